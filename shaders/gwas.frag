@@ -3,11 +3,11 @@
 layout (location = 0) out vec4 f_color;
 
 layout (location = 0) in vec3 barycentric;
-
-vec4 border_color = vec4(0.2, 0.2, 0.9, 1.0);
-vec4 center_color = vec4(0.1, 0.1, 0.4, 1.0);
+layout (location = 1) in vec4 color;
 
 void main() {
+  vec4 border_color = color;
+  vec4 center_color = vec4(0.45 * color.rgb, color.a);
 
   float dist = distance(barycentric, vec3(0.5));
 
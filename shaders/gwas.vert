@@ -1,52 +1,111 @@
 #version 450
 
-layout (location = 0) in vec2 position;
+// the marker mesh, shared by all points
+layout (location = 0) in vec2 offset;
+layout (location = 1) in vec3 barycentric_in;
+
+// the point, one per instance
+layout (location = 2) in vec2 position;
+layout (location = 3) in float value;
+layout (location = 4) in float selected;
 
 layout (location = 0) out vec3 barycentric;
+layout (location = 1) out vec4 color;
+
+// which marker this is, for `gwas_pick.frag`
+layout (location = 2) flat out uint pick_source;
+layout (location = 3) flat out uint pick_instance;
 
+// `params` holds the -log10(p) at the baseline of the track and at
+// its far end, the -log10(p) above which points are highlighted, or 0
+// if none are, and the dataset and chromosome the points are picked as
+//
+// `compression` holds the -log10(p) above which the axis is
+// compressed, and the scale it's compressed by
+//
+// `color_params` holds how the points are coloured (0: all `color`,
+// 1: `palette[value]`, 2: `value` through the colour map in
+// `palette`), the number of colours in `palette`, and the range of
+// `value` the colour map spans
 layout (set = 0, binding = 0) uniform UBO {
   mat4 view_transform;
   vec4 params;
+  vec4 compression;
+  vec4 color;
+  vec4 color_params;
+  vec4 palette[8];
 } ubo;
 
+// the colour of points lacking a value
+const vec4 missing_color = vec4(0.6, 0.6, 0.6, 1.0);
+
 out gl_PerVertex {
   vec4 gl_Position;
 };
 
-float neg_log_10(in float p) {
-  float log10 = log(10.0);
-  float p_log = log(p) / log10;
+// The vertex y-coordinates are -log10(p), computed on the CPU in
+// double precision; any past the far end of the axis are drawn there.
+// `view_transform` maps the axis, once compressed, onto the track
+float axis_units(in float neg_log10_p) {
+  float v = min(neg_log10_p, ubo.params.y);
+  float cutoff = ubo.compression.x;
 
-  float neg_p_log = -p_log;
-
-  float max_y = -(log(ubo.params.x) / log10);
+  if (v > cutoff) {
+    return cutoff + (v - cutoff) * ubo.compression.y;
+  }
 
-  return neg_p_log / max_y;
+  return v;
 }
 
+vec4 point_color() {
+  int mode = int(ubo.color_params.x);
+  int count = int(ubo.color_params.y);
+
+  if (mode == 0) {
+    return ubo.color;
+  }
+
+  if (isnan(value)) {
+    return missing_color;
+  }
+
+  if (mode == 1) {
+    return ubo.palette[clamp(int(value), 0, count - 1)];
+  }
+
+  float min_value = ubo.color_params.z;
+  float max_value = ubo.color_params.w;
+
+  float t = clamp((value - min_value) / (max_value - min_value), 0.0, 1.0);
+  float x = t * float(count - 1);
+  int ix = min(int(floor(x)), count - 2);
+
+  return mix(ubo.palette[ix], ubo.palette[ix + 1], x - float(ix));
+}
 
 void main() {
-  float y = 1.5 * neg_log_10(position.y);
-  vec4 pos = ubo.view_transform * vec4(position.x, y, 0.0, 1.0);
-
-  float b_x;
-  float b_y;
-  float b_z;
-
-  float del = 0.035;
-
-  if ((gl_VertexIndex % 3) == 0) {
-    barycentric = vec3(1.0, 0.0, 0.0);
-    pos.y += del;
-  } else if ((gl_VertexIndex % 3) == 1) {
-    barycentric = vec3(0.0, 1.0, 0.0);
-    pos.x -= (del * 0.717);
-    pos.y -= (del * 0.717);
-  } else {
-    barycentric = vec3(0.0, 0.0, 1.0);
-    pos.x += (del * 0.717);
-    pos.y -= (del * 0.717);
+  vec4 pos = ubo.view_transform * vec4(position.x, axis_units(position.y), 0.0, 1.0);
+
+  // points below the baseline would be drawn over the next track
+  if (position.y < ubo.params.x) {
+    pos.x = -10.0;
   }
 
+  bool highlight = ubo.params.z > 0.0 && position.y >= ubo.params.z;
+  float marker_scale = highlight ? 1.6 : 1.0;
+
+  // selected points are drawn again on top of the rest, larger still
+  if (selected > 0.0) {
+    marker_scale = 2.2;
+  }
+
+  pos.xy += marker_scale * offset;
+  barycentric = barycentric_in;
+
+  color = point_color();
+
+  pick_source = uint(ubo.params.w);
+  pick_instance = uint(gl_InstanceIndex);
+
   gl_Position = vec4(pos.xy, 0.0, 1.0f);
 }
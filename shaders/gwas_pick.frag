@@ -0,0 +1,18 @@
+#version 450
+
+layout (location = 0) out uvec2 f_id;
+
+layout (location = 0) in vec3 barycentric;
+layout (location = 1) in vec4 color;
+
+layout (location = 2) flat in uint pick_source;
+layout (location = 3) flat in uint pick_instance;
+
+void main() {
+  // the outline of the circle `gwas.frag` draws
+  if (distance(barycentric, vec3(0.5)) > 0.5) {
+    discard;
+  }
+
+  f_id = uvec2(pick_source, pick_instance);
+}
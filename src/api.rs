@@ -0,0 +1,417 @@
+//! The handle through which a host page controls the browser and
+//! listens for its events.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crossbeam::channel::{Receiver, Sender};
+use wasm_bindgen::prelude::*;
+use winit::event_loop::EventLoopProxy;
+
+use crate::config::{self, DatasetConfig, Region};
+use crate::coordinates::CoordinateSystem;
+use crate::gwas::{Coloring, GwasRecord, Threshold, TrackLayout, YAxisMode};
+use crate::selection::Selection;
+use crate::state::SharedState;
+use crate::view::View;
+use crate::{AppEvent, CHR_PADDING};
+
+/// Requests from the handle, handled by the event loop.
+pub enum Command {
+    LoadDataset(usize, DatasetConfig),
+    RemoveDataset(usize),
+    SetView(Region),
+    SetLayout(TrackLayout),
+    SetColoring(Coloring),
+    SetThresholds(Vec<Threshold>),
+    SetHighlight(bool),
+    SetYAxis(YAxisMode),
+    SetCompressAbove(Option<f32>),
+    /// Shows the given -log10(p) range along every track, or leaves
+    /// it to the y-axis mode.
+    SetYRange(Option<(f32, f32)>),
+    SetGpuPicking(bool),
+    /// Makes a name refer to a chromosome of the coordinate system in
+    /// the datasets loaded after it.
+    AddChrAlias(String, String),
+}
+
+/// The JS functions registered for the browser's events.
+#[derive(Default)]
+pub struct Callbacks {
+    on_hover: Option<js_sys::Function>,
+    on_click: Option<js_sys::Function>,
+    on_view_change: Option<js_sys::Function>,
+    on_selection_change: Option<js_sys::Function>,
+}
+
+// the callbacks are taken out of the `RefCell` before being called,
+// as they may well use the handle to replace themselves
+impl Callbacks {
+    pub fn hover(this: &RefCell<Self>, arg: &JsValue) {
+        let callback = this.borrow().on_hover.clone();
+        call(callback, arg);
+    }
+
+    pub fn click(this: &RefCell<Self>, arg: &JsValue) {
+        let callback = this.borrow().on_click.clone();
+        call(callback, arg);
+    }
+
+    pub fn view_change(this: &RefCell<Self>, arg: &JsValue) {
+        let callback = this.borrow().on_view_change.clone();
+        call(callback, arg);
+    }
+
+    pub fn selection_change(this: &RefCell<Self>, arg: &JsValue) {
+        let callback = this.borrow().on_selection_change.clone();
+        call(callback, arg);
+    }
+}
+
+fn call(callback: Option<js_sys::Function>, arg: &JsValue) {
+    if let Some(callback) = callback {
+        if let Err(err) = callback.call1(&JsValue::NULL, arg) {
+            web_sys::console::error_2(&"Error in callback:".into(), &err);
+        }
+    }
+}
+
+/// The event loop's end of a `Browser` handle.
+pub struct BrowserShared {
+    pub commands: Receiver<Command>,
+    pub state: SharedState,
+    pub coords: Rc<RefCell<CoordinateSystem>>,
+    pub callbacks: Rc<RefCell<Callbacks>>,
+}
+
+/// A handle to a running browser, returned by `start`.
+#[wasm_bindgen]
+pub struct Browser {
+    commands: Sender<Command>,
+    proxy: EventLoopProxy<AppEvent>,
+
+    next_dataset_id: usize,
+
+    state: SharedState,
+    coords: Rc<RefCell<CoordinateSystem>>,
+    callbacks: Rc<RefCell<Callbacks>>,
+}
+
+impl Browser {
+    pub fn new(proxy: EventLoopProxy<AppEvent>) -> (Self, BrowserShared) {
+        let (commands, commands_rx) = crossbeam::channel::unbounded();
+
+        let state = SharedState::default();
+        let coords = Rc::new(RefCell::new(CoordinateSystem::default()));
+        let callbacks = Rc::new(RefCell::new(Callbacks::default()));
+
+        let shared = BrowserShared {
+            commands: commands_rx,
+            state: state.clone(),
+            coords: coords.clone(),
+            callbacks: callbacks.clone(),
+        };
+
+        let browser = Self {
+            commands,
+            proxy,
+
+            next_dataset_id: 0,
+
+            state,
+            coords,
+            callbacks,
+        };
+
+        (browser, shared)
+    }
+
+    /// Starts loading a dataset, returning its ID.
+    pub fn load(&mut self, dataset: DatasetConfig) -> usize {
+        let id = self.next_dataset_id;
+        self.next_dataset_id += 1;
+
+        self.send(Command::LoadDataset(id, dataset));
+
+        id
+    }
+
+    /// Shows a chromosome, or a range of one.
+    pub fn show_region(&self, region: Region) {
+        self.send(Command::SetView(region));
+    }
+
+    fn send(&self, command: Command) {
+        self.commands.send(command).ok();
+        self.proxy.send_event(AppEvent::RequestRedraw).ok();
+    }
+}
+
+#[wasm_bindgen]
+impl Browser {
+    /// Starts loading a dataset, and returns the ID to remove it by.
+    /// `format` is one of the format names accepted in the
+    /// configuration; if it's left out, it's guessed from the URL.
+    pub fn load_dataset(
+        &mut self,
+        url: &str,
+        format: Option<String>,
+        label: Option<String>,
+    ) -> Result<usize, JsValue> {
+        let mut dataset = DatasetConfig::new(url);
+
+        if let Some(format) = format {
+            dataset.format = config::parse_format(&format, url)
+                .ok_or_else(|| JsValue::from_str(&format!("unknown format `{}`", format)))?;
+        }
+
+        if let Some(label) = label {
+            dataset.label = label;
+        }
+
+        Ok(self.load(dataset))
+    }
+
+    /// Makes `alias` refer to the chromosome `chr` of the coordinate
+    /// system, for datasets that name chromosomes differently. Records
+    /// of datasets already loaded that were left out for being on an
+    /// unknown chromosome are added if the alias is for it. Case, a
+    /// `chr` prefix, `M` for `MT` and PLINK's numbering of the sex
+    /// chromosomes are already taken care of.
+    pub fn add_chr_alias(&self, alias: String, chr: String) {
+        self.send(Command::AddChrAlias(alias, chr));
+    }
+
+    /// Removes a dataset, or stops it from loading.
+    pub fn remove_dataset(&self, id: usize) {
+        self.send(Command::RemoveDataset(id));
+    }
+
+    /// Shows `start..end` of the chromosome `chr`, which may be named
+    /// by any of its aliases.
+    pub fn set_view(&self, chr: String, start: usize, end: usize) -> Result<(), JsValue> {
+        if start >= end {
+            return Err(JsValue::from_str("`start` must be less than `end`"));
+        }
+
+        self.show_region(Region {
+            chr,
+            range: Some((start, end)),
+        });
+
+        Ok(())
+    }
+
+    /// Arranges the tracks either `"stacked"`, or as `"miami"` plots
+    /// of pairs of datasets, in the order they were loaded.
+    pub fn set_layout(&self, layout: &str) -> Result<(), JsValue> {
+        let layout = TrackLayout::from_name(layout)
+            .ok_or_else(|| JsValue::from_str(&format!("unknown layout `{}`", layout)))?;
+
+        self.send(Command::SetLayout(layout));
+
+        Ok(())
+    }
+
+    /// Colours the points by `"track"`, alternating shades by
+    /// `"chromosome"`, by `"annotation"`, or by `"beta"`, `"af"` or
+    /// `"ld_r2"` through the colour map `map`: `"viridis"`, `"magma"`
+    /// or `"rdbu"`. If `map` is left out, effect sizes use `"rdbu"`
+    /// and everything else `"viridis"`.
+    pub fn set_coloring(&self, by: &str, map: Option<String>) -> Result<(), JsValue> {
+        let coloring = config::parse_coloring(by, map.as_deref())?;
+
+        self.send(Command::SetColoring(coloring));
+
+        Ok(())
+    }
+
+    /// Draws a line across each track at each of `thresholds`, which
+    /// are p-values or `"bonferroni"`, for 0.05 divided by the number
+    /// of SNPs in the track's dataset.
+    pub fn set_thresholds(&self, thresholds: js_sys::Array) -> Result<(), JsValue> {
+        let thresholds = config::thresholds_from_js(&thresholds)?;
+
+        self.send(Command::SetThresholds(thresholds));
+
+        Ok(())
+    }
+
+    /// Whether to draw the points above the strictest threshold
+    /// larger than the rest.
+    pub fn set_highlight(&self, highlight: bool) {
+        self.send(Command::SetHighlight(highlight));
+    }
+
+    /// How far the y-axis of each track reaches: `"auto"` to fit the
+    /// points in view, `"fixed:<max>"` for a fixed -log10(p), or
+    /// `"cap:<max>"` to fit the whole dataset up to a -log10(p).
+    /// Points beyond a fixed or capped axis are drawn at its end.
+    pub fn set_y_axis(&self, mode: &str) -> Result<(), JsValue> {
+        let mode = YAxisMode::parse(mode)
+            .ok_or_else(|| JsValue::from_str(&format!("unknown y-axis mode `{}`", mode)))?;
+
+        self.send(Command::SetYAxis(mode));
+
+        Ok(())
+    }
+
+    /// Compresses the part of each y-axis above the -log10(p)
+    /// `cutoff` into the top quarter of the track, or stops doing so
+    /// if `cutoff` is left out.
+    pub fn set_compressed_axis(&self, cutoff: Option<f32>) -> Result<(), JsValue> {
+        if cutoff.map_or(false, |c| !(c > 0.0)) {
+            return Err(JsValue::from_str("`cutoff` must be a positive -log10(p)"));
+        }
+
+        self.send(Command::SetCompressAbove(cutoff));
+
+        Ok(())
+    }
+
+    /// Shows `min..max` of -log10(p) along every track, regardless of
+    /// the y-axis mode.
+    pub fn set_y_range(&self, min: f32, max: f32) -> Result<(), JsValue> {
+        if !(min >= 0.0 && min < max) {
+            return Err(JsValue::from_str(
+                "`min` must be at least 0 and less than `max`",
+            ));
+        }
+
+        self.send(Command::SetYRange(Some((min, max))));
+
+        Ok(())
+    }
+
+    /// Undoes any vertical zooming and panning, and fits the y-axes
+    /// to the points in view.
+    pub fn fit_y(&self) {
+        self.send(Command::SetYRange(None));
+        self.send(Command::SetYAxis(YAxisMode::Auto));
+    }
+
+    /// Whether to find the marker under the mouse on the GPU, which
+    /// always finds the topmost one however densely they're drawn,
+    /// rather than by searching for the nearest one. It stays off for
+    /// coordinate systems with more than 256 chromosomes.
+    pub fn set_gpu_picking(&self, enabled: bool) {
+        self.send(Command::SetGpuPicking(enabled));
+    }
+
+    /// The current view as `{ chr, start, end }`, where `chr` is the
+    /// chromosome in the middle of the view, and `start` and `end`
+    /// are clamped to it. `null` until the coordinate system has
+    /// loaded.
+    pub fn get_view(&self) -> JsValue {
+        view_js(&self.coords.borrow(), self.state.view.load())
+    }
+
+    /// The selected records, in the order they were selected, as
+    /// objects with the ID of the dataset they're from and the fields
+    /// of the record: `{ dataset, chr, pos, rs, allele1, allele0, af,
+    /// beta, se, p, negLog10P }`. Fields the record lacks are `null`.
+    pub fn get_selection(&self) -> JsValue {
+        selection_js(&self.state.selection.lock().unwrap())
+    }
+
+    /// Deselects every record.
+    pub fn clear_selection(&self) {
+        self.state.selection.lock().unwrap().clear();
+        self.proxy.send_event(AppEvent::RequestRedraw).ok();
+    }
+
+    /// Calls `callback` with the same array `get_selection` returns
+    /// whenever the selection changes. Pass `null` to remove it.
+    pub fn on_selection_change(&self, callback: Option<js_sys::Function>) {
+        self.callbacks.borrow_mut().on_selection_change = callback;
+    }
+
+    /// Calls `callback` with `{ chr, pos }` when the mouse moves over
+    /// a chromosome. Pass `null` to remove it.
+    pub fn on_hover(&self, callback: Option<js_sys::Function>) {
+        self.callbacks.borrow_mut().on_hover = callback;
+    }
+
+    /// Calls `callback` with `{ chr, pos }` when a chromosome is
+    /// clicked, but not at the end of a drag. Pass `null` to remove
+    /// it.
+    pub fn on_click(&self, callback: Option<js_sys::Function>) {
+        self.callbacks.borrow_mut().on_click = callback;
+    }
+
+    /// Calls `callback` with the same object `get_view` returns
+    /// whenever the view changes. Pass `null` to remove it.
+    pub fn on_view_change(&self, callback: Option<js_sys::Function>) {
+        self.callbacks.borrow_mut().on_view_change = callback;
+    }
+}
+
+/// The `{ chr, start, end }` object describing `view`.
+pub fn view_js(coords: &CoordinateSystem, view: View) -> JsValue {
+    let (start, end) = view.bp_range();
+    let range = (start.max(0.0) as usize, end.max(0.0) as usize);
+
+    let locus = match coords.locus_of_range(CHR_PADDING, range) {
+        Some(locus) => locus,
+        None => return JsValue::NULL,
+    };
+
+    let obj = js_sys::Object::new();
+    set(&obj, "chr", &locus.chr.as_str().into());
+    set(&obj, "start", &(locus.start as f64).into());
+    set(&obj, "end", &(locus.end as f64).into());
+    obj.into()
+}
+
+/// The `{ chr, pos }` object describing a genome-wide position, or
+/// `None` if it's between chromosomes.
+pub fn position_js(coords: &CoordinateSystem, pos: f32) -> Option<JsValue> {
+    if pos < 0.0 {
+        return None;
+    }
+
+    let (chr, pos) = coords.chr_pos(CHR_PADDING, pos as usize)?;
+
+    let obj = js_sys::Object::new();
+    set(&obj, "chr", &chr.into());
+    set(&obj, "pos", &(pos as f64).into());
+    Some(obj.into())
+}
+
+/// The array of the selected records `Browser::get_selection`
+/// returns.
+pub fn selection_js(selection: &Selection) -> JsValue {
+    selection
+        .records()
+        .iter()
+        .map(|selected| record_js(selected.dataset, &selected.record))
+        .collect::<js_sys::Array>()
+        .into()
+}
+
+fn record_js(dataset: usize, record: &GwasRecord) -> JsValue {
+    let opt_str = |value: &Option<String>| match value {
+        Some(value) => JsValue::from_str(value),
+        None => JsValue::NULL,
+    };
+    let opt_f64 = |value: Option<f64>| value.map_or(JsValue::NULL, JsValue::from_f64);
+
+    let obj = js_sys::Object::new();
+    set(&obj, "dataset", &(dataset as f64).into());
+    set(&obj, "chr", &record.chr.as_str().into());
+    set(&obj, "pos", &(record.pos as f64).into());
+    set(&obj, "rs", &opt_str(&record.rs));
+    set(&obj, "allele1", &opt_str(&record.allele1));
+    set(&obj, "allele0", &opt_str(&record.allele0));
+    set(&obj, "af", &opt_f64(record.af));
+    set(&obj, "beta", &opt_f64(record.beta));
+    set(&obj, "se", &opt_f64(record.se));
+    set(&obj, "p", &record.p().into());
+    set(&obj, "negLog10P", &record.neg_log10_p.into());
+    obj.into()
+}
+
+fn set(obj: &js_sys::Object, key: &str, value: &JsValue) {
+    js_sys::Reflect::set(obj, &key.into(), value).ok();
+}
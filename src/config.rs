@@ -0,0 +1,552 @@
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::coordinates::{split_locus, CoordinateSystem, Locus};
+use crate::gwas::{
+    ColorBy, ColorMap, Coloring, GwasFormat, TableLayout, Threshold, TrackLayout, YAxisMode,
+};
+use crate::view::View;
+
+const DEFAULT_COORDINATES_URL: &str = "http://localhost:8080/mouse_chrs.json";
+const DEFAULT_DATASET_URL: &str = "http://localhost:8080/gwas.json";
+
+/// Where to load a dataset from, and how to read it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DatasetConfig {
+    pub url: String,
+    pub format: GwasFormat,
+    pub label: String,
+
+    /// The colour of the dataset's track; if `None`, the next colour
+    /// of the default palette is used.
+    pub color: Option<[f32; 4]>,
+}
+
+impl DatasetConfig {
+    /// A dataset with its format guessed from the URL, and labelled
+    /// with the file name.
+    pub fn new(url: &str) -> Self {
+        let label = url.rsplit('/').next().unwrap_or(url).to_string();
+
+        Self {
+            url: url.to_string(),
+            format: GwasFormat::from_url(url),
+            label,
+
+            color: None,
+        }
+    }
+}
+
+/// A chromosome, or a range of one, to show at startup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub chr: String,
+    pub range: Option<(usize, usize)>,
+}
+
+impl Region {
+    /// Parses `chr`, `chr:pos` or `chr:start-end`, see
+    /// `coordinates::split_locus`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (chr, range) = split_locus(text)?;
+
+        Some(Self {
+            chr: chr.to_string(),
+            range,
+        })
+    }
+
+    /// A view showing the region, with the chromosomes laid out with
+    /// `padding` between them. The chromosome is found with
+    /// `find_chr`, so it may be given by any of its aliases, and the
+    /// range is clamped to it. Returns `None` if the coordinate
+    /// system has no such chromosome, or the range is empty once
+    /// clamped.
+    pub fn view(&self, coords: &CoordinateSystem, padding: usize) -> Option<View> {
+        let chr = coords.find_chr(&self.chr)?;
+        let (start, end) = self.range.unwrap_or((0, coords.chr_len(chr)?));
+
+        let locus = coords.clamp(&Locus {
+            chr: chr.to_string(),
+            start,
+            end,
+        })?;
+
+        if locus.start == locus.end {
+            return None;
+        }
+
+        let (start, end) = coords.genome_range(padding, &locus)?;
+
+        let mut view = View {
+            center: (start + end) as f32 / 2.0,
+            ..View::default()
+        };
+
+        // the view spans 2 units of clip space
+        view.scale = 0.5 * view.base_bp_width * (end - start) as f32;
+
+        Some(view)
+    }
+}
+
+/// What the browser loads and shows at startup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppConfig {
+    pub coordinates_url: String,
+    pub datasets: Vec<DatasetConfig>,
+
+    /// The genes the search box can find by symbol, as a JSON array
+    /// of `{ name, chr, start, end }` objects.
+    pub genes_url: Option<String>,
+
+    /// Further names of chromosomes of the coordinate system, as
+    /// `(alias, chr)` pairs, for datasets that name them differently.
+    pub chr_aliases: Vec<(String, String)>,
+
+    /// If `None`, the whole genome is shown.
+    pub region: Option<Region>,
+
+    pub layout: TrackLayout,
+
+    pub coloring: Coloring,
+
+    /// The significance thresholds drawn across each track.
+    pub thresholds: Vec<Threshold>,
+    /// Draw the points above the strictest threshold larger.
+    pub highlight_significant: bool,
+
+    pub y_axis: YAxisMode,
+    /// The -log10(p) above which the y-axes are compressed, so that
+    /// a single large peak leaves room for the rest.
+    pub compress_above: Option<f32>,
+
+    /// Find the marker under the mouse by reading it back from an
+    /// offscreen target the points are drawn into, rather than by
+    /// searching for the nearest one. Ignored for coordinate systems
+    /// with more than 256 chromosomes.
+    pub gpu_picking: bool,
+
+    /// Skip rows of the datasets that can't be read, rather than
+    /// failing to load the dataset.
+    pub skip_bad_rows: bool,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            coordinates_url: DEFAULT_COORDINATES_URL.to_string(),
+            datasets: vec![DatasetConfig::new(DEFAULT_DATASET_URL)],
+
+            genes_url: None,
+
+            chr_aliases: Vec::new(),
+
+            region: None,
+
+            layout: TrackLayout::default(),
+
+            coloring: Coloring::default(),
+
+            thresholds: Threshold::defaults(),
+            highlight_significant: false,
+
+            y_axis: YAxisMode::default(),
+            compress_above: None,
+
+            gpu_picking: false,
+
+            skip_bad_rows: true,
+        }
+    }
+}
+
+impl AppConfig {
+    /// The defaults, overridden by the query string of the page, e.g.
+    /// `?coords=grcm38.json&data=a.assoc.txt&data=GCST90000123.tsv.gz&region=11`.
+    ///
+    /// `data` can be given several times; the `format` and `label`
+    /// parameters apply to the `data` parameter in the same position.
+    pub fn from_query() -> Self {
+        let mut config = Self::default();
+
+        let search = web_sys::window().and_then(|window| window.location().search().ok());
+
+        let params = match search.and_then(|s| web_sys::UrlSearchParams::new_with_str(&s).ok()) {
+            Some(params) => params,
+            None => return config,
+        };
+
+        let get_all = |name: &str| -> Vec<String> {
+            params
+                .get_all(name)
+                .iter()
+                .filter_map(|value| value.as_string())
+                .collect()
+        };
+
+        if let Some(url) = params.get("coords") {
+            config.coordinates_url = url;
+        }
+
+        if let Some(url) = params.get("genes") {
+            config.genes_url = Some(url);
+        }
+
+        // a comma-separated list of `alias:chr` pairs
+        if let Some(aliases) = params.get("chr_aliases") {
+            config.chr_aliases = aliases
+                .split(',')
+                .filter_map(|pair| {
+                    let (alias, chr) = pair.split_once(':')?;
+                    Some((alias.trim().to_string(), chr.trim().to_string()))
+                })
+                .collect();
+        }
+
+        let urls = get_all("data");
+
+        if !urls.is_empty() {
+            let formats = get_all("format");
+            let labels = get_all("label");
+
+            config.datasets = urls
+                .iter()
+                .enumerate()
+                .map(|(ix, url)| {
+                    let mut dataset = DatasetConfig::new(url);
+
+                    if let Some(format) = formats.get(ix).and_then(|f| parse_format(f, url)) {
+                        dataset.format = format;
+                    }
+
+                    if let Some(label) = labels.get(ix) {
+                        dataset.label = label.clone();
+                    }
+
+                    dataset
+                })
+                .collect();
+        }
+
+        if let Some(region) = params.get("region") {
+            config.region = Region::parse(&region);
+        }
+
+        if let Some(layout) = params
+            .get("layout")
+            .and_then(|l| TrackLayout::from_name(&l))
+        {
+            config.layout = layout;
+        }
+
+        if let Some(by) = params.get("color_by") {
+            let map = params.get("color_map");
+
+            if let Ok(coloring) = parse_coloring(&by, map.as_deref()) {
+                config.coloring = coloring;
+            }
+        }
+
+        // a comma-separated list, which may be empty to hide the lines
+        if let Some(thresholds) = params.get("thresholds") {
+            config.thresholds = thresholds.split(',').filter_map(Threshold::parse).collect();
+        }
+
+        if let Some(highlight) = params.get("highlight") {
+            config.highlight_significant = highlight != "false" && highlight != "0";
+        }
+
+        if let Some(mode) = params.get("y_axis").and_then(|m| YAxisMode::parse(&m)) {
+            config.y_axis = mode;
+        }
+
+        if let Some(cutoff) = params.get("compress_above") {
+            config.compress_above = cutoff.parse::<f32>().ok().filter(|c| *c > 0.0);
+        }
+
+        if let Some(picking) = params.get("gpu_picking") {
+            config.gpu_picking = picking != "false" && picking != "0";
+        }
+
+        if let Some(skip) = params.get("skip_bad_rows") {
+            config.skip_bad_rows = skip != "false" && skip != "0";
+        }
+
+        config
+    }
+
+    /// Overrides the fields of `self` that are set in a JS object
+    /// like the following, where every field is optional:
+    ///
+    /// ```js
+    /// {
+    ///   coordinates: "grcm38.json",
+    ///   genes: "grcm38_genes.json",
+    ///   chrAliases: { "20": "X", chrUn: "Un" },
+    ///   datasets: [
+    ///     "a.assoc.txt",
+    ///     { url: "b.tsv", format: "ssf", label: "B", color: "#e66101" },
+    ///     { url: "c.regenie", format: "regenie", annotationColumn: "CSQ" },
+    ///   ],
+    ///   region: "11:60000000-70000000",
+    ///   layout: "miami",
+    ///   colorBy: "beta",
+    ///   colorMap: "rdbu",
+    ///   thresholds: [5e-8, "bonferroni"],
+    ///   highlight: true,
+    ///   yAxis: "cap:50",
+    ///   compressAbove: 20,
+    ///   gpuPicking: true,
+    ///   skipBadRows: false,
+    /// }
+    /// ```
+    pub fn apply_js(&mut self, obj: &JsValue) -> Result<(), JsValue> {
+        if obj.is_undefined() || obj.is_null() {
+            return Ok(());
+        }
+
+        let get = |obj: &JsValue, key: &str| {
+            js_sys::Reflect::get(obj, &key.into())
+                .ok()
+                .filter(|value| !value.is_undefined() && !value.is_null())
+        };
+
+        if let Some(url) = get(obj, "coordinates") {
+            self.coordinates_url = url
+                .as_string()
+                .ok_or_else(|| JsValue::from_str("`coordinates` must be a URL"))?;
+        }
+
+        if let Some(url) = get(obj, "genes") {
+            self.genes_url = Some(
+                url.as_string()
+                    .ok_or_else(|| JsValue::from_str("`genes` must be a URL"))?,
+            );
+        }
+
+        if let Some(aliases) = get(obj, "chrAliases") {
+            let aliases: js_sys::Object = aliases
+                .dyn_into()
+                .map_err(|_| JsValue::from_str("`chrAliases` must be an object"))?;
+
+            self.chr_aliases = js_sys::Object::entries(&aliases)
+                .iter()
+                .map(|entry| {
+                    let entry: js_sys::Array = entry.unchecked_into();
+                    let alias = entry.get(0).as_string().unwrap_or_default();
+                    let chr = entry.get(1).as_string().ok_or_else(|| {
+                        JsValue::from_str("`chrAliases` must map names to chromosome names")
+                    })?;
+
+                    Ok((alias, chr))
+                })
+                .collect::<Result<_, JsValue>>()?;
+        }
+
+        if let Some(datasets) = get(obj, "datasets") {
+            let datasets: js_sys::Array = datasets
+                .dyn_into()
+                .map_err(|_| JsValue::from_str("`datasets` must be an array"))?;
+
+            self.datasets = datasets
+                .iter()
+                .map(|dataset| dataset_from_js(&dataset))
+                .collect::<Result<_, _>>()?;
+        }
+
+        if let Some(region) = get(obj, "region") {
+            let region = region.as_string().and_then(|r| Region::parse(&r));
+            self.region = Some(region.ok_or_else(|| {
+                JsValue::from_str("`region` must be a chromosome or `chr:start-end`")
+            })?);
+        }
+
+        if let Some(layout) = get(obj, "layout") {
+            self.layout = layout
+                .as_string()
+                .and_then(|l| TrackLayout::from_name(&l))
+                .ok_or_else(|| JsValue::from_str("`layout` must be \"stacked\" or \"miami\""))?;
+        }
+
+        if let Some(by) = get(obj, "colorBy") {
+            let by = by
+                .as_string()
+                .ok_or_else(|| JsValue::from_str("`colorBy` must be a string"))?;
+            let map = get(obj, "colorMap").and_then(|map| map.as_string());
+
+            self.coloring = parse_coloring(&by, map.as_deref())?;
+        }
+
+        if let Some(thresholds) = get(obj, "thresholds") {
+            self.thresholds = thresholds_from_js(&thresholds)?;
+        }
+
+        if let Some(highlight) = get(obj, "highlight") {
+            self.highlight_significant = highlight
+                .as_bool()
+                .ok_or_else(|| JsValue::from_str("`highlight` must be a boolean"))?;
+        }
+
+        if let Some(mode) = get(obj, "yAxis") {
+            self.y_axis = mode
+                .as_string()
+                .and_then(|m| YAxisMode::parse(&m))
+                .ok_or_else(|| {
+                    JsValue::from_str("`yAxis` must be \"auto\", \"fixed:<max>\" or \"cap:<max>\"")
+                })?;
+        }
+
+        if let Some(cutoff) = get(obj, "compressAbove") {
+            let cutoff = cutoff
+                .as_f64()
+                .filter(|c| *c > 0.0)
+                .ok_or_else(|| JsValue::from_str("`compressAbove` must be a positive -log10(p)"))?;
+            self.compress_above = Some(cutoff as f32);
+        }
+
+        if let Some(picking) = get(obj, "gpuPicking") {
+            self.gpu_picking = picking
+                .as_bool()
+                .ok_or_else(|| JsValue::from_str("`gpuPicking` must be a boolean"))?;
+        }
+
+        if let Some(skip) = get(obj, "skipBadRows") {
+            self.skip_bad_rows = skip
+                .as_bool()
+                .ok_or_else(|| JsValue::from_str("`skipBadRows` must be a boolean"))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn dataset_from_js(value: &JsValue) -> Result<DatasetConfig, JsValue> {
+    if let Some(url) = value.as_string() {
+        return Ok(DatasetConfig::new(&url));
+    }
+
+    let get_string = |key: &str| {
+        js_sys::Reflect::get(value, &key.into())
+            .ok()
+            .and_then(|v| v.as_string())
+    };
+
+    let url = get_string("url").ok_or_else(|| JsValue::from_str("dataset has no `url`"))?;
+
+    let mut dataset = DatasetConfig::new(&url);
+
+    if let Some(format) = get_string("format") {
+        dataset.format = parse_format(&format, &url)
+            .ok_or_else(|| JsValue::from_str(&format!("unknown format `{}`", format)))?;
+    }
+
+    if let Some(label) = get_string("label") {
+        dataset.label = label;
+    }
+
+    let annotation = get_string("annotationColumn");
+    let ld_r2 = get_string("ldR2Column");
+
+    if annotation.is_some() || ld_r2.is_some() {
+        let mut mapping =
+            match dataset.format.table_layout() {
+                Some(TableLayout::Mapped(mapping)) => mapping,
+                _ => return Err(JsValue::from_str(
+                    "`annotationColumn` and `ldR2Column` need a table format with fixed columns",
+                )),
+            };
+
+        mapping.annotation = annotation;
+        mapping.ld_r2 = ld_r2;
+
+        dataset.format = GwasFormat::Mapped(mapping);
+    }
+
+    if let Some(color) = get_string("color") {
+        dataset.color = Some(
+            parse_color(&color)
+                .ok_or_else(|| JsValue::from_str(&format!("invalid color `{}`", color)))?,
+        );
+    }
+
+    Ok(dataset)
+}
+
+/// Parses a CSS-style `#rrggbb` colour.
+pub fn parse_color(text: &str) -> Option<[f32; 4]> {
+    let hex = text.strip_prefix('#')?;
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    let channel = |ix: usize| {
+        let value = u8::from_str_radix(hex.get(ix..ix + 2)?, 16).ok()?;
+        Some(value as f32 / 255.0)
+    };
+
+    Some([channel(0)?, channel(2)?, channel(4)?, 1.0])
+}
+
+/// Reads an array of thresholds, each either a p-value or
+/// `"bonferroni"`.
+pub fn thresholds_from_js(value: &JsValue) -> Result<Vec<Threshold>, JsValue> {
+    let array: &js_sys::Array = value
+        .dyn_ref()
+        .ok_or_else(|| JsValue::from_str("`thresholds` must be an array"))?;
+
+    array
+        .iter()
+        .map(|threshold| {
+            let text = match threshold.as_f64() {
+                Some(p) => p.to_string(),
+                None => threshold.as_string().unwrap_or_default(),
+            };
+
+            Threshold::parse(&text).ok_or_else(|| {
+                JsValue::from_str("thresholds must be p-values in (0, 1] or \"bonferroni\"")
+            })
+        })
+        .collect()
+}
+
+/// Parses the name of what to colour points by, and optionally the
+/// colour map to use, as used in the configuration.
+pub fn parse_coloring(by: &str, map: Option<&str>) -> Result<Coloring, JsValue> {
+    let by = ColorBy::from_name(by)
+        .ok_or_else(|| JsValue::from_str(&format!("unknown colouring `{}`", by)))?;
+
+    let mut coloring = Coloring::new(by);
+
+    if let Some(map) = map {
+        coloring.map = ColorMap::from_name(map)
+            .ok_or_else(|| JsValue::from_str(&format!("unknown colour map `{}`", map)))?;
+    }
+
+    Ok(coloring)
+}
+
+/// Parses a format name as used in the configuration; `auto` guesses
+/// the format from `url`.
+pub fn parse_format(name: &str, url: &str) -> Option<GwasFormat> {
+    use crate::gwas::{ColumnMapping, GemmaPValue};
+
+    let format = match name.to_ascii_lowercase().as_str() {
+        "auto" => GwasFormat::from_url(url),
+        "json" => GwasFormat::Json,
+        "gemma" | "gemma:wald" => GwasFormat::Gemma(GemmaPValue::Wald),
+        "gemma:lrt" => GwasFormat::Gemma(GemmaPValue::Lrt),
+        "gemma:score" => GwasFormat::Gemma(GemmaPValue::Score),
+        "plink" => GwasFormat::Plink,
+        "regenie" => GwasFormat::Mapped(ColumnMapping::regenie()),
+        "saige" => GwasFormat::Mapped(ColumnMapping::saige()),
+        "bolt-lmm" => GwasFormat::Mapped(ColumnMapping::bolt_lmm()),
+        "ssf" => GwasFormat::Ssf,
+        "binary" => GwasFormat::Binary,
+        _ => return None,
+    };
+
+    Some(format)
+}
@@ -1,13 +1,165 @@
+use std::collections::HashMap;
+use std::fmt;
+
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
+use crate::error::LoadError;
+
+/// Parses a position in basepairs, such as `1200000`, `1,200,000`,
+/// `1.2M` or `1200kb`. Thousands separators are ignored, and the
+/// suffixes `k`, `M` and `G`, with an optional `b`, are allowed.
+pub fn parse_position(text: &str) -> Option<usize> {
+    let text = text.trim().replace(',', "").to_ascii_lowercase();
+    let text = text.strip_suffix('b').unwrap_or(&text);
+
+    let multiplier = match text.chars().last()? {
+        'k' => 1e3,
+        'm' => 1e6,
+        'g' => 1e9,
+        _ => return text.parse().ok(),
+    };
+
+    let value = text[..text.len() - 1].parse::<f64>().ok()? * multiplier;
+
+    if value >= 0.0 && value.is_finite() {
+        Some(value.round() as usize)
+    } else {
+        None
+    }
+}
+
+/// Splits `chr`, `chr:pos` or `chr:start-end` into the chromosome
+/// name and the range, with positions as read by `parse_position`. A
+/// single position is read as the range of the one basepair at it.
+pub fn split_locus(text: &str) -> Option<(&str, Option<(usize, usize)>)> {
+    let text = text.trim();
+
+    let (chr, range) = match text.split_once(':') {
+        Some((chr, range)) => match range.split_once('-') {
+            Some((start, end)) => {
+                let start = parse_position(start)?;
+                let end = parse_position(end)?;
+
+                if start >= end {
+                    return None;
+                }
+
+                (chr, Some((start, end)))
+            }
+            None => {
+                let pos = parse_position(range)?;
+                (chr, Some((pos, pos + 1)))
+            }
+        },
+        None => (text, None),
+    };
+
+    if chr.is_empty() {
+        None
+    } else {
+        Some((chr, range))
+    }
+}
+
+/// Formats a position in basepairs with thousands separators, e.g.
+/// `1,200,000`.
+pub fn format_position(pos: usize) -> String {
+    let digits = pos.to_string();
+    let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (ix, digit) in digits.chars().enumerate() {
+        if ix > 0 && (digits.len() - ix) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+
+    formatted
+}
+
+/// A range of a chromosome, in basepairs from its start. `end` is
+/// exclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locus {
+    pub chr: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl fmt::Display for Locus {
+    /// Formats the locus as `chr:start-end`, which `parse_locus`
+    /// reads back.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}",
+            self.chr,
+            format_position(self.start),
+            format_position(self.end)
+        )
+    }
+}
+
+#[derive(Debug, Default)]
 pub struct CoordinateSystem {
     name: String,
 
     chrs: Vec<(String, usize)>,
+
+    /// The index in `chrs` of the chromosome each name refers to,
+    /// keyed by `chr_key`: the chromosomes' own names, the built-in
+    /// aliases, and those added with `add_chr_alias`.
+    aliases: HashMap<String, usize>,
+}
+
+/// Alternative names of the same genome assembly, e.g. the UCSC
+/// name for a GRC assembly.
+const ASSEMBLY_ALIASES: &[&[&str]] = &[
+    &["GRCh38", "hg38"],
+    &["GRCh37", "hg19"],
+    &["GRCm39", "mm39"],
+    &["GRCm38", "mm10"],
+];
+
+/// Alternative names of the same chromosome, as compared by
+/// `chr_key`. PLINK's `XY`, the pseudoautosomal region, is given in
+/// the coordinates of X.
+const CHR_ALIASES: &[&[&str]] = &[&["mt", "m"], &["x", "xy"]];
+
+/// The chromosomes PLINK numbers after the autosomes, in order.
+const NUMBERED_AFTER_AUTOSOMES: [&str; 4] = ["x", "y", "xy", "mt"];
+
+/// The key chromosome names are compared by, ignoring case and a
+/// `chr` prefix.
+fn chr_key(name: &str) -> String {
+    let lower = name.to_ascii_lowercase();
+
+    match lower.strip_prefix("chr") {
+        Some(rest) if !rest.is_empty() => rest.to_string(),
+        _ => lower,
+    }
 }
 
 impl CoordinateSystem {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether `assembly` names the same genome assembly as this
+    /// coordinate system, ignoring case and known aliases.
+    pub fn matches_assembly(&self, assembly: &str) -> bool {
+        let canonical = |name: &str| {
+            ASSEMBLY_ALIASES
+                .iter()
+                .find(|names| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+                .map(|names| names[0].to_string())
+                .unwrap_or_else(|| name.to_ascii_lowercase())
+        };
+
+        canonical(&self.name) == canonical(assembly)
+    }
+
     pub fn chr_offsets(&self, padding: usize) -> Vec<(String, usize)> {
         let mut offset = 0;
 
@@ -35,6 +187,117 @@ impl CoordinateSystem {
             .collect()
     }
 
+    /// The chromosome a genome-wide position falls on, as laid out
+    /// by `chr_offsets`, along with the position relative to the
+    /// start of the chromosome.
+    ///
+    /// Positions in the padding after a chromosome count as that
+    /// chromosome, so the relative position can exceed its length.
+    pub fn locate(&self, padding: usize, pos: usize) -> Option<(&str, usize)> {
+        let mut offset = 0;
+
+        for (ix, (name, len)) in self.chrs.iter().enumerate() {
+            let next = offset + len + padding;
+
+            if pos < next || ix == self.chrs.len() - 1 {
+                return Some((name, pos.saturating_sub(offset)));
+            }
+
+            offset = next;
+        }
+
+        None
+    }
+
+    /// The genome-wide position, as laid out by `chr_offsets`, of the
+    /// position `pos` on the chromosome `chr`.
+    pub fn genome_pos(&self, padding: usize, chr: &str, pos: usize) -> Option<usize> {
+        let mut offset = 0;
+
+        for (name, len) in self.chrs.iter() {
+            if name == chr {
+                return Some(offset + pos);
+            }
+
+            offset += len + padding;
+        }
+
+        None
+    }
+
+    /// The chromosome a genome-wide position is on, and the position
+    /// on it, or `None` if it's between chromosomes or past the last.
+    pub fn chr_pos(&self, padding: usize, pos: usize) -> Option<(&str, usize)> {
+        let (chr, pos) = self.locate(padding, pos)?;
+
+        if pos < self.chr_len(chr)? {
+            Some((chr, pos))
+        } else {
+            None
+        }
+    }
+
+    /// The genome-wide range, as laid out by `chr_offsets`, of
+    /// `locus`, or `None` if its end is before its start. The range
+    /// isn't clamped to the chromosome, see `clamp`.
+    pub fn genome_range(&self, padding: usize, locus: &Locus) -> Option<(usize, usize)> {
+        let start = self.genome_pos(padding, &locus.chr, locus.start)?;
+        let len = locus.end.checked_sub(locus.start)?;
+        Some((start, start + len))
+    }
+
+    /// The part of the genome-wide range `start..end` that's on the
+    /// chromosome its middle falls on. The locus is empty if the
+    /// middle is in the padding after the chromosome.
+    pub fn locus_of_range(&self, padding: usize, (start, end): (usize, usize)) -> Option<Locus> {
+        let middle = start + end.saturating_sub(start) / 2;
+        let (chr, middle_pos) = self.locate(padding, middle)?;
+        let chr_start = middle - middle_pos;
+
+        self.clamp(&Locus {
+            chr: chr.to_string(),
+            start: start.saturating_sub(chr_start),
+            end: end.saturating_sub(chr_start),
+        })
+    }
+
+    /// `locus` with its range clamped to its chromosome, which leaves
+    /// it empty if it's entirely past the end, or `None` if the
+    /// chromosome isn't in the coordinate system.
+    pub fn clamp(&self, locus: &Locus) -> Option<Locus> {
+        let len = self.chr_len(&locus.chr)?;
+        let start = locus.start.min(len);
+
+        Some(Locus {
+            chr: locus.chr.clone(),
+            start,
+            end: locus.end.min(len).max(start),
+        })
+    }
+
+    /// Parses `chr`, `chr:pos` or `chr:start-end`, as `split_locus`
+    /// does, into a locus on a chromosome of the coordinate system. The
+    /// chromosome is found with `find_chr`, a chromosome on its own is
+    /// the whole of it, and the range is clamped to the chromosome.
+    /// Returns `None` if that leaves it empty.
+    pub fn parse_locus(&self, text: &str) -> Option<Locus> {
+        let (chr, range) = split_locus(text)?;
+        let chr = self.find_chr(chr)?;
+        let (start, end) = range.unwrap_or((0, self.chr_len(chr)?));
+
+        let locus = self.clamp(&Locus {
+            chr: chr.to_string(),
+            start,
+            end,
+        })?;
+
+        if locus.start < locus.end {
+            Some(locus)
+        } else {
+            None
+        }
+    }
+
     pub fn chr_names(&self) -> impl Iterator<Item = &str> + '_ {
         self.chrs.iter().map(|(name, _)| name.as_str())
     }
@@ -43,52 +306,136 @@ impl CoordinateSystem {
         &self.chrs
     }
 
-    pub fn chr_len(&self, chr: &str) -> Option<usize> {
-        let (_chr, len) = self.chrs.iter().find(|(name, _)| name == chr)?;
-        Some(*len)
+    /// The name the coordinate system gives the chromosome `name`,
+    /// ignoring case and a `chr` prefix on either, and resolving
+    /// aliases: `M` and `MT` for the mitochondrial chromosome, the
+    /// numbers PLINK gives the chromosomes after the autosomes, e.g.
+    /// `23` for X in human and `20` in mouse, and any added with
+    /// `add_chr_alias`.
+    pub fn find_chr(&self, name: &str) -> Option<&str> {
+        self.chr_names().find(|chr| *chr == name).or_else(|| {
+            let ix = *self.aliases.get(&chr_key(name))?;
+            Some(self.chrs[ix].0.as_str())
+        })
+    }
+
+    /// Makes `alias` refer to the chromosome `chr`, which is itself
+    /// found with `find_chr`. Returns `false` if there's no such
+    /// chromosome.
+    pub fn add_chr_alias(&mut self, alias: &str, chr: &str) -> bool {
+        match self.find_chr(chr).and_then(|chr| self.chr_index(chr)) {
+            Some(ix) => {
+                self.aliases.insert(chr_key(alias), ix);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn chr_index(&self, chr: &str) -> Option<usize> {
+        self.chrs.iter().position(|(name, _)| name == chr)
     }
 
-    pub async fn fetch_and_parse(url: &str) -> Result<Self, JsValue> {
-        use wasm_bindgen_futures::JsFuture;
-        use web_sys::{Request, RequestInit, Response};
+    /// Fills `aliases` with the chromosomes' own names and the
+    /// built-in aliases, neither of which replace a chromosome's own
+    /// name.
+    fn index_aliases(&mut self) {
+        let mut aliases: HashMap<String, usize> = HashMap::new();
+
+        for (ix, (name, _)) in self.chrs.iter().enumerate() {
+            aliases.entry(chr_key(name)).or_insert(ix);
+        }
+
+        for names in CHR_ALIASES {
+            let ix = names.iter().find_map(|name| aliases.get(*name).copied());
+
+            if let Some(ix) = ix {
+                for name in names.iter() {
+                    aliases.entry(name.to_string()).or_insert(ix);
+                }
+            }
+        }
+
+        let autosomes = aliases
+            .keys()
+            .filter_map(|key| key.parse::<usize>().ok())
+            .max()
+            .unwrap_or(0);
+
+        if autosomes > 0 {
+            for (offset, name) in NUMBERED_AFTER_AUTOSOMES.iter().enumerate() {
+                if let Some(&ix) = aliases.get(*name) {
+                    let number = autosomes + offset + 1;
+                    aliases.entry(number.to_string()).or_insert(ix);
+                }
+            }
+        }
 
-        let window = web_sys::window().unwrap();
+        self.aliases = aliases;
+    }
 
-        let mut opts = RequestInit::new();
-        opts.method("GET");
+    pub fn chr_len(&self, chr: &str) -> Option<usize> {
+        let (_chr, len) = self.chrs.iter().find(|(name, _)| name == chr)?;
+        Some(*len)
+    }
 
-        // TODO handle errors correctly
-        let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+    pub async fn fetch_and_parse(url: &str) -> Result<Self, LoadError> {
+        use crate::fetch::{fetch, js_message, response_text};
 
-        let resp_value = JsFuture::from(window.fetch_with_request(&request))
-            .await
-            .unwrap();
+        let resp = fetch(url).await?;
+        let text = response_text(resp).await?;
 
-        let resp: Response = resp_value.dyn_into().unwrap();
-        let json = JsFuture::from(resp.json().unwrap()).await.unwrap();
+        let json = js_sys::JSON::parse(&text).map_err(|err| {
+            LoadError::Parse(format!(
+                "error parsing coordinate system: {}",
+                js_message(&err)
+            ))
+        })?;
 
         Self::parse_js(json)
     }
 
-    pub fn parse_js(obj: JsValue) -> Result<Self, JsValue> {
-        let name = js_sys::Reflect::get(&obj, &"name".into())?;
-        let name = name.as_string().unwrap();
+    /// Reads a coordinate system from an object with a `name` and a
+    /// `chrs` array of `{ name, len }` objects. Chromosomes are
+    /// numbered from 1 in errors.
+    pub fn parse_js(obj: JsValue) -> Result<Self, LoadError> {
+        let get = |obj: &JsValue, key: &str| js_sys::Reflect::get(obj, &key.into()).ok();
+
+        let name = get(&obj, "name")
+            .and_then(|name| name.as_string())
+            .ok_or_else(|| LoadError::Parse("coordinate system has no `name`".into()))?;
 
-        let chrs = js_sys::Reflect::get(&obj, &"chrs".into())?;
-        let chrs_array: js_sys::Array = chrs.dyn_into()?;
+        let chrs_array: js_sys::Array = get(&obj, "chrs")
+            .and_then(|chrs| chrs.dyn_into().ok())
+            .ok_or_else(|| LoadError::Parse("coordinate system has no `chrs` array".into()))?;
 
         let mut chrs: Vec<(String, usize)> = Vec::new();
 
-        for chr in chrs_array.iter() {
-            let name = js_sys::Reflect::get(&chr, &"name".into())?;
-            let name = name.as_string().unwrap();
+        for (ix, chr) in chrs_array.iter().enumerate() {
+            let missing = |field: &str| LoadError::MissingField {
+                row: ix + 1,
+                field: field.to_string(),
+            };
 
-            let len = js_sys::Reflect::get(&chr, &"len".into())?;
-            let len = (len.as_f64().unwrap()) as usize;
+            let name = get(&chr, "name")
+                .and_then(|name| name.as_string())
+                .ok_or_else(|| missing("name"))?;
 
-            chrs.push((name, len));
+            let len = get(&chr, "len")
+                .and_then(|len| len.as_f64())
+                .ok_or_else(|| missing("len"))?;
+
+            chrs.push((name, len as usize));
         }
 
-        Ok(Self { name, chrs })
+        let mut coords = Self {
+            name,
+            chrs,
+            aliases: HashMap::new(),
+        };
+
+        coords.index_aliases();
+
+        Ok(coords)
     }
 }
@@ -0,0 +1,70 @@
+use std::fmt::Formatter;
+
+/// An error encountered while fetching or parsing a coordinate
+/// system or dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadError {
+    /// The request failed before a response arrived.
+    Network { url: String, message: String },
+    /// The server responded, but not with a success status.
+    HttpStatus { url: String, status: u16 },
+    /// The data as a whole couldn't be parsed, e.g. invalid JSON or
+    /// a truncated gzip stream.
+    Parse(String),
+    /// A table lacks a required column.
+    MissingColumn { column: String, field: String },
+    /// A row lacks a required field. Rows are numbered from 1, and
+    /// are line numbers for delimited tables.
+    MissingField { row: usize, field: String },
+    /// A field holds something that isn't a valid value for it.
+    InvalidValue {
+        row: usize,
+        field: String,
+        value: String,
+    },
+    /// Records refer to a chromosome the coordinate system lacks.
+    UnknownChromosome { chr: String, rows: usize },
+}
+
+impl LoadError {
+    /// Whether the error only concerns a single row, which can be
+    /// skipped without giving up on the rest of the data.
+    pub fn is_row_error(&self) -> bool {
+        matches!(
+            self,
+            LoadError::MissingField { .. }
+                | LoadError::InvalidValue { .. }
+                | LoadError::UnknownChromosome { .. }
+        )
+    }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Network { url, message } => {
+                write!(f, "could not fetch {}: {}", url, message)
+            }
+            LoadError::HttpStatus { url, status } => {
+                write!(f, "{} responded with HTTP status {}", url, status)
+            }
+            LoadError::Parse(msg) => write!(f, "{}", msg),
+            LoadError::MissingColumn { column, field } => {
+                write!(f, "missing column `{}` for {}", column, field)
+            }
+            LoadError::MissingField { row, field } => {
+                write!(f, "row {}: missing field `{}`", row, field)
+            }
+            LoadError::InvalidValue { row, field, value } => {
+                write!(f, "row {}: invalid value `{}` for `{}`", row, value, field)
+            }
+            LoadError::UnknownChromosome { chr, rows } => write!(
+                f,
+                "chromosome `{}` is not in the coordinate system ({} rows)",
+                chr, rows
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
@@ -0,0 +1,213 @@
+use std::io::{Read, Write};
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestInit, Response};
+
+use crate::error::LoadError;
+
+type Result<T> = std::result::Result<T, LoadError>;
+
+/// The first two bytes of every gzip member, including the BGZF
+/// blocks used by `bgzip`.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Requests `url`, failing unless the server responds with a
+/// success status.
+pub async fn fetch(url: &str) -> Result<Response> {
+    let network_err = |err: JsValue| LoadError::Network {
+        url: url.to_string(),
+        message: js_message(&err),
+    };
+
+    let window = web_sys::window().ok_or_else(|| network_err("no window".into()))?;
+
+    let mut opts = RequestInit::new();
+    opts.method("GET");
+
+    let request = Request::new_with_str_and_init(&url, &opts).map_err(network_err)?;
+
+    let resp_value = JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(network_err)?;
+
+    let resp: Response = resp_value.dyn_into().map_err(network_err)?;
+
+    if !resp.ok() {
+        return Err(LoadError::HttpStatus {
+            url: url.to_string(),
+            status: resp.status(),
+        });
+    }
+
+    Ok(resp)
+}
+
+/// Reads the whole response body, decompressing it if it's gzipped.
+pub async fn response_bytes(resp: Response) -> Result<Vec<u8>> {
+    let network_err = |err: JsValue| LoadError::Network {
+        url: resp.url(),
+        message: js_message(&err),
+    };
+
+    let promise = resp.array_buffer().map_err(network_err)?;
+    let buffer = JsFuture::from(promise).await.map_err(network_err)?;
+
+    let bytes = js_sys::Uint8Array::new(&buffer).to_vec();
+
+    decompress(bytes)
+}
+
+pub async fn response_text(resp: Response) -> Result<String> {
+    let bytes = response_bytes(resp).await?;
+    String::from_utf8(bytes).map_err(|_| LoadError::Parse("response is not valid UTF-8".into()))
+}
+
+/// A readable message for an error thrown by a browser API.
+pub fn js_message(err: &JsValue) -> String {
+    err.dyn_ref::<js_sys::Error>()
+        .map(|err| String::from(err.message()))
+        .or_else(|| err.as_string())
+        .unwrap_or_else(|| format!("{:?}", err))
+}
+
+fn gzip_err(err: std::io::Error) -> LoadError {
+    LoadError::Parse(format!("error decompressing gzip data: {}", err))
+}
+
+/// Decompresses `bytes` if they start with the gzip magic bytes, and
+/// returns them untouched otherwise.
+///
+/// BGZF files are a series of concatenated gzip members, so all
+/// members are decoded rather than only the first.
+pub fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>> {
+    if !bytes.starts_with(&GZIP_MAGIC) {
+        return Ok(bytes);
+    }
+
+    let mut decoder = flate2::read::MultiGzDecoder::new(bytes.as_slice());
+    let mut output = Vec::new();
+
+    decoder.read_to_end(&mut output).map_err(gzip_err)?;
+
+    Ok(output)
+}
+
+/// Reads a response body chunk by chunk as it arrives, rather than
+/// waiting for all of it.
+pub struct BodyReader {
+    url: String,
+    reader: JsValue,
+    read: js_sys::Function,
+}
+
+impl BodyReader {
+    pub fn new(resp: &Response) -> Option<Self> {
+        let body = resp.body()?;
+
+        // `ReadableStream` has no methods in this version of web-sys
+        let get_reader: js_sys::Function = js_sys::Reflect::get(&body, &"getReader".into())
+            .ok()?
+            .dyn_into()
+            .ok()?;
+
+        let reader = get_reader.call0(&body).ok()?;
+
+        let read = js_sys::Reflect::get(&reader, &"read".into())
+            .ok()?
+            .dyn_into()
+            .ok()?;
+
+        Some(Self {
+            url: resp.url(),
+            reader,
+            read,
+        })
+    }
+
+    /// The next chunk of the body, or `None` once it's all been read.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        let url = &self.url;
+        let js_err = |err: JsValue| LoadError::Network {
+            url: url.clone(),
+            message: js_message(&err),
+        };
+
+        let promise: js_sys::Promise = self
+            .read
+            .call0(&self.reader)
+            .map_err(js_err)?
+            .dyn_into()
+            .map_err(js_err)?;
+
+        let result = JsFuture::from(promise).await.map_err(js_err)?;
+
+        let done = js_sys::Reflect::get(&result, &"done".into()).map_err(js_err)?;
+
+        if done.as_bool().unwrap_or(true) {
+            return Ok(None);
+        }
+
+        let value = js_sys::Reflect::get(&result, &"value".into()).map_err(js_err)?;
+
+        Ok(Some(js_sys::Uint8Array::new(&value).to_vec()))
+    }
+}
+
+/// Decompresses a body that arrives in chunks, deciding from its
+/// first bytes whether it's gzipped at all.
+pub enum StreamDecoder {
+    Undecided(Vec<u8>),
+    Plain,
+    Gzip(flate2::write::MultiGzDecoder<Vec<u8>>),
+}
+
+impl Default for StreamDecoder {
+    fn default() -> Self {
+        StreamDecoder::Undecided(Vec::new())
+    }
+}
+
+impl StreamDecoder {
+    /// Returns the decompressed bytes that are available after
+    /// feeding in `chunk`.
+    pub fn push(&mut self, chunk: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            StreamDecoder::Undecided(buf) => {
+                buf.extend_from_slice(chunk);
+
+                if buf.len() < GZIP_MAGIC.len() {
+                    return Ok(Vec::new());
+                }
+
+                let buf = std::mem::take(buf);
+
+                if buf.starts_with(&GZIP_MAGIC) {
+                    *self = StreamDecoder::Gzip(flate2::write::MultiGzDecoder::new(Vec::new()));
+                    self.push(&buf)
+                } else {
+                    *self = StreamDecoder::Plain;
+                    Ok(buf)
+                }
+            }
+            StreamDecoder::Plain => Ok(chunk.to_vec()),
+            StreamDecoder::Gzip(decoder) => {
+                decoder.write_all(chunk).map_err(gzip_err)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+
+    /// Returns whatever is left once the body has been read.
+    pub fn finish(&mut self) -> Result<Vec<u8>> {
+        match self {
+            StreamDecoder::Undecided(buf) => Ok(std::mem::take(buf)),
+            StreamDecoder::Plain => Ok(Vec::new()),
+            StreamDecoder::Gzip(decoder) => {
+                decoder.try_finish().map_err(gzip_err)?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Gene locations, so that the search box can find genes by symbol.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::error::LoadError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gene {
+    pub name: String,
+    pub chr: String,
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GeneIndex {
+    genes: Vec<Gene>,
+}
+
+impl GeneIndex {
+    pub fn len(&self) -> usize {
+        self.genes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genes.is_empty()
+    }
+
+    /// The gene with the symbol `name`, ignoring case.
+    pub fn find(&self, name: &str) -> Option<&Gene> {
+        self.genes
+            .iter()
+            .find(|gene| gene.name.eq_ignore_ascii_case(name))
+    }
+
+    pub async fn fetch_and_parse(url: &str) -> Result<Self, LoadError> {
+        use crate::fetch::{fetch, js_message, response_text};
+
+        let resp = fetch(url).await?;
+        let text = response_text(resp).await?;
+
+        let json = js_sys::JSON::parse(&text).map_err(|err| {
+            LoadError::Parse(format!("error parsing genes: {}", js_message(&err)))
+        })?;
+
+        Self::parse_js(json)
+    }
+
+    /// Reads genes from an array of `{ name, chr, start, end }`
+    /// objects. Genes are numbered from 1 in errors.
+    pub fn parse_js(array: JsValue) -> Result<Self, LoadError> {
+        let get = |obj: &JsValue, key: &str| js_sys::Reflect::get(obj, &key.into()).ok();
+
+        let array: js_sys::Array = array
+            .dyn_into()
+            .map_err(|_| LoadError::Parse("genes must be an array".into()))?;
+
+        let mut genes = Vec::with_capacity(array.length() as usize);
+
+        for (ix, gene) in array.iter().enumerate() {
+            let missing = |field: &str| LoadError::MissingField {
+                row: ix + 1,
+                field: field.to_string(),
+            };
+
+            let string = |field: &str| {
+                get(&gene, field)
+                    .and_then(|value| value.as_string())
+                    .ok_or_else(|| missing(field))
+            };
+
+            let position = |field: &str| {
+                get(&gene, field)
+                    .and_then(|value| value.as_f64())
+                    .map(|value| value as usize)
+                    .ok_or_else(|| missing(field))
+            };
+
+            genes.push(Gene {
+                name: string("name")?,
+                chr: string("chr")?,
+                start: position("start")?,
+                end: position("end")?,
+            });
+        }
+
+        Ok(Self { genes })
+    }
+}
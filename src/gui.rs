@@ -11,16 +11,39 @@ use egui_winit_platform::{Platform, PlatformDescriptor};
 use nalgebra as na;
 use nalgebra_glm as glm;
 
-use crate::{geometry::Point, view::View};
+use crate::{
+    error::LoadError,
+    geometry::Point,
+    gwas::{
+        axis,
+        color::{CATEGORY_COLORS, OTHER_COLOR},
+        GwasRecord, Legend, TrackParams, YDirection,
+    },
+    selection::SelectedRecord,
+    view::View,
+};
 
 pub mod egui_wgpu;
 
 use egui_wgpu::*;
 
+/// A change to the selection made in its panel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectionAction {
+    /// Deselect the record at the given position in the list.
+    Remove(usize),
+    Clear,
+}
+
 pub struct Gui {
     pub platform: Platform,
     pub egui_rpass: RenderPass,
     pub screen_descriptor: ScreenDescriptor,
+
+    /// The text of the search box.
+    search: String,
+    /// Why the last search failed, shown under the search box.
+    pub search_error: Option<String>,
 }
 
 impl Gui {
@@ -67,7 +90,100 @@ impl Gui {
             platform,
             egui_rpass,
             screen_descriptor,
+
+            search: String::new(),
+            search_error: None,
+        }
+    }
+
+    /// Draws the search box at the top of the screen, returning its
+    /// text when enter is pressed in it.
+    pub fn draw_search(&mut self) -> Option<String> {
+        let ctx = self.platform.context();
+        let screen_rect = ctx.input().screen_rect();
+
+        let search = &mut self.search;
+        let error = &self.search_error;
+        let mut submitted = None;
+
+        egui::Area::new("search")
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 8.0))
+            .show(&ctx, |ui| {
+                egui::Frame::popup(&ctx.style()).show(ui, |ui| {
+                    ui.set_max_width(screen_rect.width() / 3.0);
+
+                    ui.horizontal(|ui| {
+                        ui.label("Search");
+                        let response = ui.text_edit_singleline(search);
+
+                        if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                            submitted = Some(search.clone());
+                        }
+                    });
+
+                    if let Some(error) = error {
+                        ui.colored_label(egui::Color32::from_rgb(220, 60, 60), error);
+                    }
+                });
+            });
+
+        submitted
+    }
+
+    /// Shades the genome-wide range `start..end` behind the tracks,
+    /// to point out what was searched for.
+    pub fn draw_search_hit(&self, view: View, (start, end): (usize, usize)) {
+        let ctx = self.platform.context();
+        let painter = ctx.layer_painter(painter_layer());
+
+        let screen_rect = ctx.input().screen_rect();
+        let to_screen_x = |bp: usize| {
+            let x = (bp as f32 - view.center) * view.base_bp_width / view.scale;
+            (x + 1.0) * 0.5 * screen_rect.width()
+        };
+
+        // at least a couple of pixels wide, so a single variant shows
+        let (left, right) = (to_screen_x(start), to_screen_x(end));
+        let center = (left + right) / 2.0;
+        let half_width = ((right - left) / 2.0).max(1.0);
+
+        painter.rect_filled(
+            egui::Rect::from_min_max(
+                egui::pos2(center - half_width, screen_rect.top()),
+                egui::pos2(center + half_width, screen_rect.bottom()),
+            ),
+            0.0,
+            egui::Color32::from_rgba_unmultiplied(255, 220, 100, 30),
+        );
+    }
+
+    /// Circles the marker of the record a search found, at the
+    /// genome-wide position `bp` with the -log10(p) value `value` in
+    /// `track`. It's kept apart from the selection, which is the
+    /// user's own.
+    pub fn draw_search_record(&self, view: View, track: &TrackParams, bp: usize, value: f32) {
+        if value < track.min_y {
+            return;
         }
+
+        let ctx = self.platform.context();
+        let painter = ctx.layer_painter(painter_layer());
+
+        let screen_rect = ctx.input().screen_rect();
+
+        let x = (bp as f32 - view.center) * view.base_bp_width / view.scale;
+        let y = track.y_of(value.min(track.max_y));
+
+        let center = egui::pos2(
+            (x + 1.0) * 0.5 * screen_rect.width(),
+            (1.0 - y) * 0.5 * screen_rect.height(),
+        );
+
+        painter.circle_stroke(
+            center,
+            8.0,
+            egui::Stroke::new(2.0, egui::Color32::from_rgb(255, 200, 60)),
+        );
     }
 
     pub fn draw_chr_labels(
@@ -107,6 +223,401 @@ impl Gui {
             }
         }
     }
+
+    /// Draws the label of each track in its colour, at the far end
+    /// of the track from its baseline. Tracks that grow downward get
+    /// mirrored labels.
+    pub fn draw_track_labels(&self, tracks: &[(&str, TrackParams)]) {
+        let ctx = self.platform.context();
+        let painter = ctx.layer_painter(painter_layer());
+
+        let screen_rect = ctx.input().screen_rect();
+        let to_screen_y = |y: f32| (1.0 - y) * 0.5 * screen_rect.height();
+
+        for (label, track) in tracks {
+            let color = to_color32(track.color);
+
+            let label_align = match track.direction {
+                YDirection::Up => egui::Align2::LEFT_TOP,
+                YDirection::Down => egui::Align2::LEFT_BOTTOM,
+            };
+
+            let far_y = to_screen_y(track.far_end());
+
+            painter.text(
+                egui::pos2(8.0, far_y),
+                label_align,
+                label,
+                egui::TextStyle::Body,
+                color,
+            );
+        }
+    }
+
+    /// Draws a -log10(p) axis along the right edge of each track, with
+    /// ticks placed the same way `gwas.vert` places the points. If the
+    /// larger values of a track are truncated, the label at the end
+    /// of its axis says so; if they're compressed, the axis is broken
+    /// at the cutoff.
+    pub fn draw_y_axes(&self, tracks: &[TrackParams]) {
+        let ctx = self.platform.context();
+        let painter = ctx.layer_painter(painter_layer());
+
+        let screen_rect = ctx.input().screen_rect();
+        let to_screen_y = |y: f32| (1.0 - y) * 0.5 * screen_rect.height();
+
+        let axis_x = screen_rect.width() - 8.0;
+        let stroke = egui::Stroke::new(1.0, egui::Color32::GRAY);
+
+        for track in tracks {
+            painter.line_segment(
+                [
+                    egui::pos2(axis_x, to_screen_y(track.baseline())),
+                    egui::pos2(axis_x, to_screen_y(track.far_end())),
+                ],
+                stroke,
+            );
+
+            // a compressed axis gets ticks of its own above the cutoff,
+            // and a break mark at it
+            let (cutoff, scale) = track.compression();
+            let segments = if scale < 1.0 {
+                let y = to_screen_y(track.y_of(cutoff));
+                painter.line_segment(
+                    [
+                        egui::pos2(axis_x - 4.0, y + 2.0),
+                        egui::pos2(axis_x + 4.0, y - 2.0),
+                    ],
+                    stroke,
+                );
+                painter.line_segment(
+                    [
+                        egui::pos2(axis_x - 4.0, y + 5.0),
+                        egui::pos2(axis_x + 4.0, y + 1.0),
+                    ],
+                    stroke,
+                );
+
+                vec![(track.min_y, cutoff), (cutoff, track.max_y)]
+            } else {
+                vec![(track.min_y, track.max_y)]
+            };
+
+            for (ix, &(min, max)) in segments.iter().enumerate() {
+                let step = axis::tick_step(max - min);
+                let mut ticks = axis::ticks(min, max);
+
+                // the ticks at the cutoff belong to the segment below it
+                if ix > 0 {
+                    ticks.retain(|t| t - min > step * 1e-3);
+                }
+
+                let last_segment = ix == segments.len() - 1;
+
+                if last_segment
+                    && track.truncated
+                    && ticks.last().map_or(true, |t| max - t > step * 1e-3)
+                {
+                    ticks.push(max);
+                }
+
+                for (tick_ix, value) in ticks.iter().enumerate() {
+                    let y = to_screen_y(track.y_of(*value));
+
+                    painter
+                        .line_segment([egui::pos2(axis_x - 4.0, y), egui::pos2(axis_x, y)], stroke);
+
+                    let mut label = if step >= 1.0 {
+                        format!("{:.0}", value)
+                    } else {
+                        format!("{:.1}", value)
+                    };
+
+                    if last_segment && track.truncated && tick_ix == ticks.len() - 1 {
+                        label.push('+');
+                    }
+
+                    painter.text(
+                        egui::pos2(axis_x - 6.0, y),
+                        egui::Align2::RIGHT_CENTER,
+                        label,
+                        egui::TextStyle::Small,
+                        egui::Color32::GRAY,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Draws a line across each track at each of its thresholds, given
+    /// as -log10(p) with a label, strictest first. The strictest is
+    /// drawn in red and the rest in grey; thresholds beyond the top of
+    /// a track, or below its baseline, aren't drawn.
+    pub fn draw_thresholds(&self, tracks: &[(TrackParams, &[(f32, String)])]) {
+        let ctx = self.platform.context();
+        let painter = ctx.layer_painter(painter_layer());
+
+        let screen_rect = ctx.input().screen_rect();
+        let to_screen_y = |y: f32| (1.0 - y) * 0.5 * screen_rect.height();
+
+        for (track, lines) in tracks {
+            for (ix, (neg_log10_p, label)) in lines.iter().enumerate() {
+                if *neg_log10_p > track.max_y || *neg_log10_p < track.min_y {
+                    continue;
+                }
+
+                let color = if ix == 0 {
+                    egui::Color32::from_rgba_unmultiplied(220, 60, 60, 200)
+                } else {
+                    egui::Color32::from_rgba_unmultiplied(160, 160, 160, 160)
+                };
+
+                let y = to_screen_y(track.y_of(*neg_log10_p));
+
+                painter.line_segment(
+                    [egui::pos2(0.0, y), egui::pos2(screen_rect.width(), y)],
+                    egui::Stroke::new(1.0, color),
+                );
+
+                let align = match track.direction {
+                    YDirection::Up => egui::Align2::LEFT_BOTTOM,
+                    YDirection::Down => egui::Align2::LEFT_TOP,
+                };
+
+                painter.text(
+                    egui::pos2(8.0, y),
+                    align,
+                    label,
+                    egui::TextStyle::Small,
+                    color,
+                );
+            }
+        }
+    }
+
+    /// Lists the errors encountered while loading data in a window,
+    /// along with the number of rows that were skipped, if any. Both
+    /// are labelled with the source they concern.
+    pub fn draw_load_errors(
+        &self,
+        errors: &[(String, LoadError)],
+        skipped_rows: &[(String, usize)],
+    ) {
+        if errors.is_empty() && skipped_rows.is_empty() {
+            return;
+        }
+
+        let ctx = self.platform.context();
+
+        egui::Window::new("Load errors")
+            .default_pos(egui::pos2(10.0, 10.0))
+            .show(&ctx, |ui| {
+                for (source, err) in errors {
+                    ui.colored_label(egui::Color32::RED, format!("{}: {}", source, err));
+                }
+
+                for (source, count) in skipped_rows {
+                    ui.label(format!(
+                        "{}: skipped {} rows that couldn't be read",
+                        source, count
+                    ));
+                }
+            });
+    }
+
+    /// Shows what the colours of the points mean, for the datasets
+    /// whose points aren't simply coloured by track.
+    pub fn draw_color_legend(&self, legends: &[(&str, Legend)]) {
+        if legends.is_empty() {
+            return;
+        }
+
+        let ctx = self.platform.context();
+
+        egui::Window::new("Legend")
+            .default_pos(egui::pos2(10.0, 120.0))
+            .show(&ctx, |ui| {
+                for (label, legend) in legends {
+                    ui.label(*label);
+
+                    match legend {
+                        Legend::Categories { names, other } => {
+                            for (name, color) in names.iter().zip(CATEGORY_COLORS.iter()) {
+                                swatch(ui, *color, name);
+                            }
+
+                            if *other {
+                                swatch(ui, OTHER_COLOR, "other");
+                            }
+                        }
+                        Legend::Range(map, min, max) => {
+                            let size = egui::vec2(128.0, 12.0);
+                            let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+
+                            let steps = 32;
+                            let step_width = rect.width() / steps as f32;
+
+                            for step in 0..steps {
+                                let left = rect.left() + step as f32 * step_width;
+                                let slice = egui::Rect::from_min_max(
+                                    egui::pos2(left, rect.top()),
+                                    egui::pos2(left + step_width, rect.bottom()),
+                                );
+
+                                let t = (step as f32 + 0.5) / steps as f32;
+                                ui.painter()
+                                    .rect_filled(slice, 0.0, to_color32(map.sample(t)));
+                            }
+
+                            ui.label(format!("{:.3} to {:.3}", min, max));
+                        }
+                    }
+                }
+            });
+    }
+
+    /// Lists the fields of `record`, from the dataset labelled
+    /// `label`, next to the mouse at `pos`, in screen space.
+    pub fn draw_record_tooltip(&self, pos: Point, label: &str, record: &GwasRecord) {
+        let ctx = self.platform.context();
+
+        egui::Area::new("record_tooltip")
+            .order(egui::Order::Tooltip)
+            .fixed_pos(egui::pos2(pos.x + 16.0, pos.y + 16.0))
+            .show(&ctx, |ui| {
+                egui::Frame::popup(&ctx.style()).show(ui, |ui| {
+                    ui.label(label);
+
+                    egui::Grid::new("record_tooltip_fields").show(ui, |ui| {
+                        let mut row = |name: &str, value: String| {
+                            ui.label(name);
+                            ui.label(value);
+                            ui.end_row();
+                        };
+
+                        if let Some(rs) = &record.rs {
+                            row("rs", rs.clone());
+                        }
+
+                        row("Position", format!("{}:{}", record.chr, record.pos));
+
+                        if let (Some(a1), Some(a0)) = (&record.allele1, &record.allele0) {
+                            row("Alleles", format!("{}/{}", a1, a0));
+                        }
+
+                        if let Some(af) = record.af {
+                            row("AF", format!("{:.4}", af));
+                        }
+
+                        if let Some(beta) = record.beta {
+                            row("Beta", format!("{:.4}", beta));
+                        }
+
+                        if let Some(se) = record.se {
+                            row("SE", format!("{:.4}", se));
+                        }
+
+                        row("p", format_p(record));
+                    });
+                });
+            });
+    }
+
+    /// Lists the selected records in a window, each with the label of
+    /// its dataset, looked up by ID in `labels`.
+    pub fn draw_selection(
+        &self,
+        selected: &[SelectedRecord],
+        labels: &HashMap<usize, &str>,
+    ) -> Option<SelectionAction> {
+        if selected.is_empty() {
+            return None;
+        }
+
+        let ctx = self.platform.context();
+        let mut action = None;
+
+        egui::Window::new("Selection")
+            .default_pos(egui::pos2(10.0, 320.0))
+            .show(&ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{} selected", selected.len()));
+
+                    if ui.button("Clear").clicked() {
+                        action = Some(SelectionAction::Clear);
+                    }
+                });
+
+                egui::ScrollArea::from_max_height(240.0).show(ui, |ui| {
+                    egui::Grid::new("selection_list").show(ui, |ui| {
+                        for (ix, selected) in selected.iter().enumerate() {
+                            let record = &selected.record;
+
+                            ui.label(labels.get(&selected.dataset).copied().unwrap_or(""));
+                            ui.label(record.rs.as_deref().unwrap_or(""));
+                            ui.label(format!("{}:{}", record.chr, record.pos));
+                            ui.label(format_p(record));
+
+                            if ui.small_button("x").clicked() {
+                                action = Some(SelectionAction::Remove(ix));
+                            }
+
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        action
+    }
+
+    /// Outlines the rectangle with the corners `a` and `b`, in screen
+    /// space, as it's being dragged out to select the records in it.
+    pub fn draw_selection_box(&self, a: Point, b: Point) {
+        let ctx = self.platform.context();
+        let painter = ctx.layer_painter(painter_layer());
+
+        let rect = egui::Rect::from_two_pos(a.into(), b.into());
+
+        painter.rect_filled(
+            rect,
+            0.0,
+            egui::Color32::from_rgba_unmultiplied(100, 150, 220, 40),
+        );
+        painter.rect_stroke(
+            rect,
+            0.0,
+            egui::Stroke::new(1.0, egui::Color32::from_rgb(100, 150, 220)),
+        );
+    }
+}
+
+/// `p` for display, written out as a power of ten if it's too small
+/// for an `f64`.
+fn format_p(record: &GwasRecord) -> String {
+    let p = record.p();
+
+    if p > 0.0 {
+        format!("{:.3e}", p)
+    } else {
+        format!("1e-{:.1}", record.neg_log10_p)
+    }
+}
+
+/// A square of `color` the size of a line of text, followed by
+/// `label`.
+fn swatch(ui: &mut egui::Ui, color: [f32; 4], label: &str) {
+    ui.horizontal(|ui| {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(12.0, 12.0), egui::Sense::hover());
+        ui.painter().rect_filled(rect, 2.0, to_color32(color));
+        ui.label(label);
+    });
+}
+
+fn to_color32(color: [f32; 4]) -> egui::Color32 {
+    let [r, g, b, a] = color;
+    let channel = |c: f32| (c.max(0.0).min(1.0) * 255.0) as u8;
+    egui::Color32::from_rgba_unmultiplied(channel(r), channel(g), channel(b), channel(a))
 }
 
 fn painter_layer() -> egui::LayerId {
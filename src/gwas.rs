@@ -1,25 +1,104 @@
 use wasm_bindgen::prelude::*;
 
+use bytemuck::{Pod, Zeroable};
+use wasm_bindgen::JsCast;
 use wgpu::util::DeviceExt;
 
 use anyhow::Result;
 
-use nalgebra_glm as glm;
-
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 
 use crate::coordinates::CoordinateSystem;
-use crate::geometry::Vertex;
+use crate::error::LoadError;
+use crate::fetch::{fetch, js_message, response_bytes, response_text, BodyReader, StreamDecoder};
 use crate::view::{View, ViewportDims};
 
+pub mod axis;
+pub mod binary;
+pub mod color;
+pub mod json;
+pub mod lod;
+pub mod picking;
+pub mod record;
+pub mod ssf;
+pub mod table;
+pub mod threshold;
+
+pub use axis::YAxisMode;
+pub use binary::BinaryChr;
+pub use color::{ColorBy, ColorMap, Coloring, Legend};
+pub use json::JsonArraySplitter;
+pub use lod::LodPyramid;
+pub use picking::{PickFrame, PickId, PickingTarget};
+pub use record::GwasRecord;
+pub use ssf::SsfMetadata;
+pub use table::{ColumnMapping, Delimiter, GemmaPValue, PValueColumn, TableLayout, TableReader};
+pub use threshold::Threshold;
+
+/// A vertex of the marker mesh drawn at every point, offset from the
+/// point in clip space. The barycentric coordinates let the fragment
+/// shader shade the triangle as a circle.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct MarkerVertex {
+    pub offset: [f32; 2],
+    pub barycentric: [f32; 3],
+}
+
+/// The size of the markers in clip space.
+pub const MARKER_SIZE: f32 = 0.035;
+
+/// How much larger highlighted markers are drawn, as in `gwas.vert`.
+const HIGHLIGHT_SCALE: f32 = 1.6;
+
+fn marker_mesh() -> [MarkerVertex; 3] {
+    let del = MARKER_SIZE;
+
+    [
+        MarkerVertex {
+            offset: [0.0, del],
+            barycentric: [1.0, 0.0, 0.0],
+        },
+        MarkerVertex {
+            offset: [-del * 0.717, -del * 0.717],
+            barycentric: [0.0, 1.0, 0.0],
+        },
+        MarkerVertex {
+            offset: [del * 0.717, -del * 0.717],
+            barycentric: [0.0, 0.0, 1.0],
+        },
+    ]
+}
+
+/// The per-point data, one per record, with the position in base
+/// pairs and -log10(p), the value the point's colour is computed
+/// from, which is NaN if the record lacks it, and 1 if the point is
+/// drawn as selected or 0 if not.
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+pub struct PointInstance {
+    pub position: [f32; 2],
+    pub value: f32,
+    pub selected: f32,
+}
+
 pub struct GwasPipeline {
     vs: wgpu::ShaderModule,
     fs: wgpu::ShaderModule,
+    pick_fs: wgpu::ShaderModule,
 
     pub bind_group_layout: wgpu::BindGroupLayout,
     pub pipeline_layout: wgpu::PipelineLayout,
 
     pub render_pipeline: wgpu::RenderPipeline,
+
+    /// Draws the points into a `PickingTarget`.
+    pub pick_pipeline: wgpu::RenderPipeline,
+
+    /// The marker every point instance is drawn with.
+    marker_buf: wgpu::Buffer,
 }
 
 impl GwasPipeline {
@@ -30,7 +109,14 @@ impl GwasPipeline {
         let fs_mod = crate::include_shader!("gwas.frag.spv");
         let fs = device.create_shader_module(&fs_mod);
 
-        let vertex_size = std::mem::size_of::<Vertex>();
+        let pick_fs_mod = crate::include_shader!("gwas_pick.frag.spv");
+        let pick_fs = device.create_shader_module(&pick_fs_mod);
+
+        let marker_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Marker mesh"),
+            contents: bytemuck::cast_slice(&marker_mesh()),
+            usage: wgpu::BufferUsage::VERTEX,
+        });
 
         let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
@@ -53,15 +139,45 @@ impl GwasPipeline {
             push_constant_ranges: &[],
         });
 
-        let vertex_buffers = [wgpu::VertexBufferLayout {
-            array_stride: vertex_size as wgpu::BufferAddress,
-            step_mode: wgpu::InputStepMode::Vertex,
-            attributes: &[wgpu::VertexAttribute {
-                format: wgpu::VertexFormat::Float32x2,
-                offset: 0,
-                shader_location: 0,
-            }],
-        }];
+        let vertex_buffers = [
+            wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<MarkerVertex>() as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Vertex,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        format: wgpu::VertexFormat::Float32x2,
+                        offset: 0,
+                        shader_location: 0,
+                    },
+                    wgpu::VertexAttribute {
+                        format: wgpu::VertexFormat::Float32x3,
+                        offset: 8,
+                        shader_location: 1,
+                    },
+                ],
+            },
+            wgpu::VertexBufferLayout {
+                array_stride: std::mem::size_of::<PointInstance>() as wgpu::BufferAddress,
+                step_mode: wgpu::InputStepMode::Instance,
+                attributes: &[
+                    wgpu::VertexAttribute {
+                        format: wgpu::VertexFormat::Float32x2,
+                        offset: 0,
+                        shader_location: 2,
+                    },
+                    wgpu::VertexAttribute {
+                        format: wgpu::VertexFormat::Float32,
+                        offset: 8,
+                        shader_location: 3,
+                    },
+                    wgpu::VertexAttribute {
+                        format: wgpu::VertexFormat::Float32,
+                        offset: 12,
+                        shader_location: 4,
+                    },
+                ],
+            },
+        ];
 
         let primitive_state = wgpu::PrimitiveState {
             topology: wgpu::PrimitiveTopology::TriangleList,
@@ -101,23 +217,53 @@ impl GwasPipeline {
             multisample: wgpu::MultisampleState::default(),
         });
 
+        // integer targets can't be blended, so the topmost marker
+        // simply overwrites the rest
+        let pick_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Picking"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &vs,
+                entry_point: "main",
+                buffers: &vertex_buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &pick_fs,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: picking::PICK_FORMAT,
+                    blend: None,
+                    write_mask: wgpu::ColorWrite::ALL,
+                }],
+            }),
+            primitive: primitive_state,
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        });
+
         Ok(Self {
             vs,
             fs,
+            pick_fs,
 
             bind_group_layout,
             pipeline_layout,
             render_pipeline,
+            pick_pipeline,
+
+            marker_buf,
         })
     }
 
+    /// Draws the marker once for each of the first `instance_count`
+    /// points in `instance_buf`.
     pub fn draw(
         &self,
         encoder: &mut wgpu::CommandEncoder,
         frame: &wgpu::SwapChainTexture,
-        vertex_buf: wgpu::BufferSlice<'_>,
+        instance_buf: wgpu::BufferSlice<'_>,
         bind_group: &wgpu::BindGroup,
-        vertex_count: usize,
+        instance_count: usize,
         clear: bool,
     ) {
         let load_op = if clear {
@@ -126,10 +272,59 @@ impl GwasPipeline {
             wgpu::LoadOp::Load
         };
 
+        self.draw_with(
+            &self.render_pipeline,
+            encoder,
+            &frame.view,
+            instance_buf,
+            bind_group,
+            instance_count,
+            load_op,
+        );
+    }
+
+    /// Draws the points like `draw`, but into `target`, which is
+    /// cleared to nothing being picked first if `clear` is set.
+    pub fn draw_picking(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &PickingTarget,
+        instance_buf: wgpu::BufferSlice<'_>,
+        bind_group: &wgpu::BindGroup,
+        instance_count: usize,
+        clear: bool,
+    ) {
+        let load_op = if clear {
+            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+        } else {
+            wgpu::LoadOp::Load
+        };
+
+        self.draw_with(
+            &self.pick_pipeline,
+            encoder,
+            &target.view,
+            instance_buf,
+            bind_group,
+            instance_count,
+            load_op,
+        );
+    }
+
+    fn draw_with(
+        &self,
+        pipeline: &wgpu::RenderPipeline,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        instance_buf: wgpu::BufferSlice<'_>,
+        bind_group: &wgpu::BindGroup,
+        instance_count: usize,
+        load_op: wgpu::LoadOp<wgpu::Color>,
+    ) {
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[wgpu::RenderPassColorAttachment {
-                view: &frame.view,
+                view,
                 resolve_target: None,
                 ops: wgpu::Operations {
                     load: load_op,
@@ -139,19 +334,249 @@ impl GwasPipeline {
             depth_stencil_attachment: None,
         });
         rpass.push_debug_group("Prepare data for draw.");
-        rpass.set_pipeline(&self.render_pipeline);
+        rpass.set_pipeline(pipeline);
         rpass.set_bind_group(0, bind_group, &[]);
-        rpass.set_vertex_buffer(0, vertex_buf);
+        rpass.set_vertex_buffer(0, self.marker_buf.slice(..));
+        rpass.set_vertex_buffer(1, instance_buf);
         rpass.pop_debug_group();
         rpass.insert_debug_marker("Draw!");
-        rpass.draw(0..(vertex_count as u32), 0..1);
+        rpass.draw(0..3, 0..(instance_count as u32));
+    }
+}
+
+/// The part of the clip space the stacked tracks share, leaving room
+/// for the chromosome labels at the bottom.
+const TRACKS_BOTTOM: f32 = -0.8;
+const TRACKS_TOP: f32 = 0.9;
+
+/// The space between stacked tracks, in clip space.
+const TRACK_GAP: f32 = 0.1;
+
+/// The space between the two halves of a Miami plot, where the
+/// chromosome labels go, in clip space.
+const MIAMI_AXIS_GAP: f32 = 0.1;
+
+/// The clip space y-coordinate of the chromosome labels below the
+/// stacked tracks.
+const CHR_AXIS_Y: f32 = -0.9;
+
+/// Which way the -log10(p) values of a track grow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YDirection {
+    Up,
+    Down,
+}
+
+impl YDirection {
+    pub fn sign(&self) -> f32 {
+        match self {
+            YDirection::Up => 1.0,
+            YDirection::Down => -1.0,
+        }
+    }
+}
+
+/// How the tracks of the datasets are arranged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackLayout {
+    /// One track per dataset, stacked on top of each other.
+    Stacked,
+    /// Datasets are paired up as Miami plots, with the first of each
+    /// pair growing up and the second growing down from a shared
+    /// chromosome axis. The pairs are stacked.
+    Miami,
+}
+
+impl Default for TrackLayout {
+    fn default() -> Self {
+        TrackLayout::Stacked
+    }
+}
+
+impl TrackLayout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "stacked" => Some(TrackLayout::Stacked),
+            "miami" => Some(TrackLayout::Miami),
+            _ => None,
+        }
+    }
+
+    fn slot_count(&self, track_count: usize) -> usize {
+        match self {
+            TrackLayout::Stacked => track_count,
+            TrackLayout::Miami => (track_count + 1) / 2,
+        }
+    }
+
+    /// The parameters of track `ix` of `count`.
+    pub fn track(&self, ix: usize, count: usize, max_y: f32, color: [f32; 4]) -> TrackParams {
+        let slots = self.slot_count(count);
+
+        match self {
+            TrackLayout::Stacked => {
+                let (bottom, height) = stacked_slot(ix, slots);
+
+                TrackParams {
+                    bottom,
+                    height,
+                    direction: YDirection::Up,
+                    min_y: 0.0,
+                    max_y,
+                    color,
+                    truncated: false,
+                    compress_above: None,
+                    highlight_above: None,
+                }
+            }
+            TrackLayout::Miami => {
+                let (bottom, height) = stacked_slot(ix / 2, slots);
+                let half = (height - MIAMI_AXIS_GAP) / 2.0;
+
+                let (bottom, direction) = if ix % 2 == 0 {
+                    (bottom + half + MIAMI_AXIS_GAP, YDirection::Up)
+                } else {
+                    (bottom, YDirection::Down)
+                };
+
+                TrackParams {
+                    bottom,
+                    height: half,
+                    direction,
+                    min_y: 0.0,
+                    max_y,
+                    color,
+                    truncated: false,
+                    compress_above: None,
+                    highlight_above: None,
+                }
+            }
+        }
+    }
+
+    /// The clip space y-coordinates to draw chromosome labels at,
+    /// given the number of tracks.
+    pub fn chr_axes(&self, count: usize) -> Vec<f32> {
+        match self {
+            TrackLayout::Stacked => vec![CHR_AXIS_Y],
+            TrackLayout::Miami => (0..self.slot_count(count))
+                .map(|slot| {
+                    let (bottom, height) = stacked_slot(slot, self.slot_count(count));
+                    bottom + height / 2.0
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The bottom and height of slot `ix` of `count` slots stacked on
+/// top of each other, the first at the top.
+fn stacked_slot(ix: usize, count: usize) -> (f32, f32) {
+    let count = count.max(1);
+
+    let total = TRACKS_TOP - TRACKS_BOTTOM;
+    let height = (total - TRACK_GAP * (count - 1) as f32) / count as f32;
+
+    let top = TRACKS_TOP - (height + TRACK_GAP) * ix as f32;
+
+    (top - height, height)
+}
+
+/// Where and how a dataset is drawn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackParams {
+    /// The bottom of the track, in clip space.
+    pub bottom: f32,
+    /// The height of the track, in clip space.
+    pub height: f32,
+
+    pub direction: YDirection,
+
+    /// The -log10(p) value at the baseline; smaller values aren't
+    /// drawn.
+    pub min_y: f32,
+    /// The -log10(p) value at the far end of the track from the
+    /// baseline.
+    pub max_y: f32,
+
+    pub color: [f32; 4],
+
+    /// Whether values larger than `max_y` are drawn at `max_y`.
+    pub truncated: bool,
+
+    /// The -log10(p) value above which the axis is compressed, see
+    /// `axis::compression_scale`.
+    pub compress_above: Option<f32>,
+
+    /// The -log10(p) value above which points are drawn larger.
+    pub highlight_above: Option<f32>,
+}
+
+impl TrackParams {
+    /// The top of the track, in clip space.
+    pub fn top(&self) -> f32 {
+        self.bottom + self.height
+    }
+
+    /// Where -log10(p) = 0 is drawn, in clip space.
+    pub fn baseline(&self) -> f32 {
+        match self.direction {
+            YDirection::Up => self.bottom,
+            YDirection::Down => self.top(),
+        }
+    }
+
+    /// Where `max_y` is drawn, in clip space.
+    pub fn far_end(&self) -> f32 {
+        match self.direction {
+            YDirection::Up => self.top(),
+            YDirection::Down => self.bottom,
+        }
+    }
+
+    /// The cutoff and scale of the compressed part of the axis; the
+    /// scale is 1 if it isn't compressed.
+    pub fn compression(&self) -> (f32, f32) {
+        match self.compress_above {
+            Some(cutoff) => (
+                cutoff,
+                axis::compression_scale(self.min_y, self.max_y, cutoff),
+            ),
+            None => (self.max_y, 1.0),
+        }
+    }
+
+    /// The position of a -log10(p) value along the axis, once it's
+    /// been compressed.
+    pub fn units(&self, neg_log10_p: f32) -> f32 {
+        let (cutoff, scale) = self.compression();
+        axis::compress(neg_log10_p, cutoff, scale)
+    }
+
+    /// Where a -log10(p) value is drawn, in clip space, the same way
+    /// `gwas.vert` places the points.
+    pub fn y_of(&self, neg_log10_p: f32) -> f32 {
+        let (min, max) = (self.min_y, self.units(self.max_y));
+        let t = (self.units(neg_log10_p) - min) / (max - min);
+        self.baseline() + self.direction.sign() * self.height * t
+    }
+
+    /// The -log10(p) value drawn at the clip space y-coordinate `y`.
+    pub fn value_at(&self, y: f32) -> f32 {
+        let (min, max) = (self.min_y, self.units(self.max_y));
+        let t = (y - self.baseline()) / (self.direction.sign() * self.height);
+
+        let (cutoff, scale) = self.compression();
+        axis::decompress(min + t * (max - min), cutoff, scale)
     }
 }
 
 pub struct GwasUniforms {
     pub uniform_bufs: HashMap<String, wgpu::Buffer>,
 
-    pub bind_groups: HashMap<String, wgpu::BindGroup>,
+    /// In the order of the chromosomes, which is the order they're
+    /// drawn in, so that the same markers are on top every frame.
+    pub bind_groups: Vec<(String, wgpu::BindGroup)>,
 }
 
 impl GwasUniforms {
@@ -161,7 +586,7 @@ impl GwasUniforms {
         chr_names: impl Iterator<Item = &'a str>,
     ) -> Self {
         let mut bufs: HashMap<String, wgpu::Buffer> = HashMap::default();
-        let mut bind_groups: HashMap<String, wgpu::BindGroup> = HashMap::default();
+        let mut bind_groups: Vec<(String, wgpu::BindGroup)> = Vec::new();
 
         let default_view = View::default();
         let matrix = default_view.to_scaled_matrix();
@@ -169,7 +594,7 @@ impl GwasUniforms {
         let mat_array = crate::view::mat4_to_array(&matrix);
         let mat_data: &[u8] = bytemuck::cast_slice(&mat_array);
 
-        let param_array = [0.0; 4];
+        let param_array = [0.0f32; 12 + 36];
         let param_data: &[u8] = bytemuck::cast_slice(&param_array);
 
         let mut data = Vec::with_capacity(mat_data.len() + param_data.len());
@@ -199,7 +624,7 @@ impl GwasUniforms {
 
             bufs.insert(name.to_string(), uniform_buf);
 
-            bind_groups.insert(name.to_string(), bind_group);
+            bind_groups.push((name.to_string(), bind_group));
         }
 
         GwasUniforms {
@@ -214,121 +639,1202 @@ impl GwasUniforms {
         queue: &wgpu::Queue,
         offsets: &[(String, usize)],
         view: View,
-        vertical_offset: f32,
-        min_y: f32,
+        dataset: usize,
+        track: &TrackParams,
+        coloring: &Coloring,
+        color_range: (f32, f32),
     ) {
-        for (name, offset) in offsets {
+        let color_data = coloring.uniform_data(color_range);
+
+        for (chr_ix, (name, offset)) in offsets.iter().enumerate() {
             // for (name, buf) in self.uniform_bufs.iter() {
             let buf = self.uniform_bufs.get(name).unwrap();
             let mut offset_view = view;
             let offset = *offset;
             offset_view.center -= offset as f32;
 
-            let matrix = offset_view.to_scaled_matrix();
-            let matrix = matrix.append_translation(&glm::vec3(0.0, vertical_offset, 0.0));
+            let units_max = track.units(track.max_y);
+            let matrix = offset_view.to_track_matrix(
+                (track.min_y, units_max),
+                (track.baseline(), track.far_end()),
+            );
 
             let mat_array = crate::view::mat4_to_array(&matrix);
             let mat_data: &[u8] = bytemuck::cast_slice(&mat_array);
 
-            let param_array = [min_y; 4];
+            let [r, g, b, a] = coloring.chr_color(track.color, chr_ix);
+            // thresholds are positive, so 0 means nothing's highlighted
+            let highlight = track.highlight_above.unwrap_or(0.0);
+            let (cutoff, scale) = track.compression();
+            let pick_source = PickId::source(dataset, chr_ix);
+            #[rustfmt::skip]
+            let param_array = [
+                track.min_y, track.max_y, highlight, pick_source,
+                cutoff, scale, 0.0, 0.0,
+                r, g, b, a,
+            ];
             let param_data: &[u8] = bytemuck::cast_slice(&param_array);
 
-            let mut data = Vec::with_capacity(mat_data.len() + param_data.len());
+            let color_data: &[u8] = bytemuck::cast_slice(&color_data);
+
+            let mut data = Vec::with_capacity(mat_data.len() + param_data.len() + color_data.len());
             data.extend_from_slice(mat_data);
             data.extend_from_slice(param_data);
+            data.extend_from_slice(color_data);
 
             queue.write_buffer(buf, 0, bytemuck::cast_slice(&data));
         }
     }
 }
 
+/// A record drawn near a point, see `GwasDataChrs::nearest_record`.
+#[derive(Debug, Clone)]
+pub struct RecordHit<'a> {
+    /// The distance from the point, in clip space.
+    pub distance: f32,
+    /// The index of the record among those of its chromosome.
+    pub index: usize,
+    pub record: Cow<'a, GwasRecord>,
+}
+
+/// The file formats `GwasDataChrs` can be loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GwasFormat {
+    /// A JSON array of objects with GEMMA's field names.
+    Json,
+    /// GEMMA's whitespace-delimited `.assoc.txt` output.
+    Gemma(GemmaPValue),
+    /// PLINK 1.9 and 2.0 association output.
+    Plink,
+    /// Any delimited table, with the columns given by the mapping.
+    Mapped(ColumnMapping),
+    /// GWAS Catalog summary statistics (GWAS-SSF), with an optional
+    /// `-meta.yaml` sidecar next to the data file.
+    Ssf,
+    /// The compact format of the `binary` module.
+    Binary,
+}
+
+impl GwasFormat {
+    /// Guesses the format from the file extension, defaulting to JSON.
+    /// The query string and fragment, and a trailing `.gz` or `.bgz`,
+    /// are ignored.
+    ///
+    /// Other tools write `.tsv` files in layouts of their own, so only
+    /// those named the way the GWAS Catalog names them, such as
+    /// `GCST90000123.tsv.gz`, are taken to be GWAS-SSF.
+    pub fn from_url(url: &str) -> Self {
+        let url = url.split(&['?', '#'][..]).next().unwrap_or(url);
+        let url = url
+            .strip_suffix(".gz")
+            .or_else(|| url.strip_suffix(".bgz"))
+            .unwrap_or(url);
+
+        let file_name = url.rsplit('/').next().unwrap_or(url);
+
+        if url.ends_with(".gwb") {
+            GwasFormat::Binary
+        } else if url.ends_with(".tsv") && file_name.starts_with("GCST") {
+            GwasFormat::Ssf
+        } else if url.ends_with(".assoc.txt") {
+            GwasFormat::Gemma(GemmaPValue::default())
+        } else if url.contains(".glm.")
+            || url.ends_with(".assoc")
+            || url.ends_with(".qassoc")
+            || url.ends_with(".assoc.linear")
+            || url.ends_with(".assoc.logistic")
+        {
+            GwasFormat::Plink
+        } else {
+            GwasFormat::Json
+        }
+    }
+
+    /// How to read the format as a delimited table, or `None` if it
+    /// isn't one.
+    pub fn table_layout(&self) -> Option<TableLayout> {
+        match self {
+            GwasFormat::Json | GwasFormat::Binary => None,
+            GwasFormat::Gemma(p_value) => Some(TableLayout::Mapped(ColumnMapping::gemma(*p_value))),
+            GwasFormat::Plink => Some(TableLayout::Plink),
+            GwasFormat::Mapped(mapping) => Some(TableLayout::Mapped(mapping.clone())),
+            GwasFormat::Ssf => Some(TableLayout::Ssf),
+        }
+    }
+}
+
+/// Options for loading a dataset.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Skip rows with missing or invalid fields, counting them, rather
+    /// than failing the whole dataset on the first one.
+    pub skip_bad_rows: bool,
+}
+
+/// What's known about a dataset once it has finished loading.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoadSummary {
+    /// The genome assembly of the data, if the source states it.
+    pub assembly: Option<String>,
+    /// The number of rows skipped due to `LoadOptions::skip_bad_rows`.
+    pub skipped_rows: usize,
+}
+
+/// Records as they're handed over while a dataset loads.
+#[derive(Debug, Clone)]
+pub enum RecordBatch {
+    /// Records parsed one by one, of any chromosomes.
+    Records(Vec<GwasRecord>),
+    /// All the records of one chromosome of a binary file, as its
+    /// columns.
+    Columns(BinaryChr<'static>),
+}
+
+impl RecordBatch {
+    pub fn len(&self) -> usize {
+        match self {
+            RecordBatch::Records(records) => records.len(),
+            RecordBatch::Columns(chr) => chr.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The records of one chromosome of a dataset. The columns of a
+/// binary file are kept as they are, and a record is only built from
+/// them when one is shown, selected or searched for.
+#[derive(Debug, Clone)]
+pub enum ChrRecords {
+    Records(Vec<GwasRecord>),
+    Columns(BinaryChr<'static>),
+}
+
+impl Default for ChrRecords {
+    fn default() -> Self {
+        ChrRecords::Records(Vec::new())
+    }
+}
+
+impl ChrRecords {
+    pub fn len(&self) -> usize {
+        match self {
+            ChrRecords::Records(records) => records.len(),
+            ChrRecords::Columns(chr) => chr.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn pos(&self, ix: usize) -> usize {
+        match self {
+            ChrRecords::Records(records) => records[ix].pos,
+            ChrRecords::Columns(chr) => chr.positions[ix] as usize,
+        }
+    }
+
+    pub fn neg_log10_p(&self, ix: usize) -> f64 {
+        match self {
+            ChrRecords::Records(records) => records[ix].neg_log10_p,
+            ChrRecords::Columns(chr) => chr.neg_log10_p.get(ix),
+        }
+    }
+
+    pub fn rs(&self, ix: usize) -> Option<&str> {
+        match self {
+            ChrRecords::Records(records) => records[ix].rs.as_deref(),
+            ChrRecords::Columns(chr) => chr.rsid(ix),
+        }
+    }
+
+    /// The indices of the records positioned within `start..=end`,
+    /// found by binary search, so only correct if the records are in
+    /// order of position.
+    pub fn indices_within(&self, start: f32, end: f32) -> Range<usize> {
+        let (first, last) = match self {
+            ChrRecords::Records(records) => (
+                records.partition_point(|r| (r.pos as f32) < start),
+                records.partition_point(|r| (r.pos as f32) <= end),
+            ),
+            ChrRecords::Columns(chr) => (
+                chr.positions.partition_point(|&pos| (pos as f32) < start),
+                chr.positions.partition_point(|&pos| (pos as f32) <= end),
+            ),
+        };
+
+        first..last.max(first)
+    }
+
+    /// The `ix`th record, if there is one.
+    pub fn get(&self, ix: usize) -> Option<Cow<'_, GwasRecord>> {
+        match self {
+            ChrRecords::Records(records) => records.get(ix).map(Cow::Borrowed),
+            ChrRecords::Columns(chr) if ix < chr.len() => Some(Cow::Owned(chr.record(ix))),
+            ChrRecords::Columns(_) => None,
+        }
+    }
+
+    /// Adds a record, building the records of the columns first if
+    /// they're what's held.
+    pub fn push(&mut self, record: GwasRecord) {
+        if let ChrRecords::Columns(chr) = self {
+            *self = ChrRecords::Records(chr.records().collect());
+        }
+
+        if let ChrRecords::Records(records) = self {
+            records.push(record);
+        }
+    }
+}
+
+/// The smallest number of points a chromosome's instance buffer is
+/// created with, so that small batches don't each cause a realloc.
+const MIN_INSTANCE_CAPACITY: usize = 4096;
+
 pub struct GwasDataChrs {
-    pub vertex_buffers: HashMap<String, wgpu::Buffer>,
-    pub vertex_counts: HashMap<String, usize>,
+    /// One `PointInstance` per record, for each chromosome.
+    pub instance_buffers: HashMap<String, wgpu::Buffer>,
+    pub instance_counts: HashMap<String, usize>,
+
+    /// The number of instances each buffer has room for.
+    instance_capacities: HashMap<String, usize>,
+
+    /// Each chromosome's points aggregated into bins, which are drawn
+    /// instead of the records when zoomed out.
+    pub lod: HashMap<String, LodPyramid>,
+
+    /// The instance buffers of the levels of each chromosome's
+    /// pyramid, with the number of instances in each.
+    lod_buffers: HashMap<String, Vec<(wgpu::Buffer, usize)>>,
+
+    /// The chromosomes whose `lod_buffers` are out of date.
+    lod_dirty: HashSet<String>,
+
+    /// The instances of the selected records of each chromosome, with
+    /// the number of them, drawn on top of the rest.
+    selection_buffers: HashMap<String, (wgpu::Buffer, usize)>,
+
+    /// What the `value` of the point instances holds.
+    color_by: ColorBy,
+
+    /// The annotations of the records, in the order they were first
+    /// seen, up to `color::MAX_CATEGORIES` of them.
+    pub categories: Vec<String>,
+    /// Whether there are more annotations than `categories` holds.
+    pub other_categories: bool,
+
+    /// The largest absolute effect size, which the colour map for
+    /// effect sizes is centered on zero with.
+    pub max_abs_beta: f32,
+
+    pub data: HashMap<String, ChrRecords>,
 
-    pub data: HashMap<String, Vec<JsValue>>,
+    /// The chromosomes whose records didn't arrive in order of
+    /// position, so can't be binary searched by it.
+    unsorted: HashSet<String>,
 
-    pub min_p: f32,
-    pub max_p: f32,
+    /// The range of the -log10(p) values over all chromosomes.
+    pub min_neg_log10_p: f32,
+    pub max_neg_log10_p: f32,
+
+    /// The genome assembly the positions refer to, if the source
+    /// states it.
+    pub assembly: Option<String>,
+
+    /// The number of rows of the source that couldn't be loaded.
+    pub skipped_rows: usize,
+}
+
+impl Default for GwasDataChrs {
+    fn default() -> Self {
+        Self {
+            instance_buffers: HashMap::default(),
+            instance_counts: HashMap::default(),
+
+            instance_capacities: HashMap::default(),
+
+            lod: HashMap::default(),
+            lod_buffers: HashMap::default(),
+            lod_dirty: HashSet::default(),
+
+            selection_buffers: HashMap::default(),
+
+            color_by: ColorBy::Track,
+
+            categories: Vec::new(),
+            other_categories: false,
+
+            max_abs_beta: 0.0,
+
+            data: HashMap::default(),
+            unsorted: HashSet::default(),
+
+            min_neg_log10_p: std::f32::MAX,
+            max_neg_log10_p: std::f32::MIN,
+
+            assembly: None,
+
+            skipped_rows: 0,
+        }
+    }
 }
 
 impl GwasDataChrs {
-    pub async fn fetch_and_parse(device: &wgpu::Device, url: &str) -> Result<Self> {
-        use wasm_bindgen::JsCast;
-        use wasm_bindgen_futures::JsFuture;
-        use web_sys::{Request, RequestInit, Response};
+    pub async fn fetch_and_parse(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        url: &str,
+    ) -> Result<Self, LoadError> {
+        let format = GwasFormat::from_url(url);
+        Self::fetch_and_parse_as(device, queue, url, format, LoadOptions::default()).await
+    }
 
-        let window = web_sys::window().unwrap();
+    pub async fn fetch_and_parse_as(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        url: &str,
+        format: GwasFormat,
+        options: LoadOptions,
+    ) -> Result<Self, LoadError> {
+        let mut batches = Vec::new();
 
-        let mut opts = RequestInit::new();
-        opts.method("GET");
+        let summary = stream_records(url, format, options, |batch| batches.push(batch)).await?;
 
-        // TODO handle errors correctly
-        let request = Request::new_with_str_and_init(&url, &opts).unwrap();
+        let mut data = Self::default();
 
-        let resp_value = JsFuture::from(window.fetch_with_request(&request))
-            .await
-            .unwrap();
+        for batch in batches {
+            data.append_batch(device, queue, batch);
+        }
 
-        let resp: Response = resp_value.dyn_into().unwrap();
-        let json = JsFuture::from(resp.json().unwrap()).await.unwrap();
-        let json_array: js_sys::Array = json.dyn_into().ok().unwrap();
+        data.update_lod_buffers(device);
+        data.assembly = summary.assembly;
+        data.skipped_rows = summary.skipped_rows;
 
-        let mut objects: HashMap<String, Vec<JsValue>> = HashMap::default();
-        let mut vertex_datas: HashMap<String, Vec<Vertex>> = HashMap::default();
+        Ok(data)
+    }
 
-        let mut min_p = std::f64::MAX;
-        let mut max_p = std::f64::MIN;
+    /// Adds a batch of records, see `append_records` and
+    /// `append_columns`.
+    pub fn append_batch(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, batch: RecordBatch) {
+        match batch {
+            RecordBatch::Records(records) => self.append_records(device, queue, records),
+            RecordBatch::Columns(chr) => self.append_columns(device, queue, chr),
+        }
+    }
 
-        for value in json_array.iter() {
-            let chr = js_sys::Reflect::get(&value, &"chr".into()).unwrap();
-            let chr = chr.as_string().unwrap();
+    /// Adds the records of a chromosome of a binary file, creating its
+    /// instances straight from the columns.
+    pub fn append_columns(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        chr: BinaryChr<'static>,
+    ) {
+        let name = chr.name.to_string();
+
+        // e.g. if the file names a chromosome in two ways
+        if self.data.contains_key(&name) {
+            let records: Vec<GwasRecord> = chr.records().collect();
+            self.append_records(device, queue, records);
+            return;
+        }
+
+        let records = ChrRecords::Columns(chr);
+        let pyramid = self.lod.entry(name.clone()).or_default();
+
+        for ix in 0..records.len() {
+            let neg_log10_p = records.neg_log10_p(ix);
+
+            self.min_neg_log10_p = self.min_neg_log10_p.min(neg_log10_p as f32);
+            self.max_neg_log10_p = self.max_neg_log10_p.max(neg_log10_p as f32);
+
+            pyramid.insert(ix, records.pos(ix), neg_log10_p);
+        }
+
+        let capacity = records.len();
+        self.data.insert(name.clone(), records);
+
+        self.reupload_instances(device, queue, &name, capacity);
+        self.lod_dirty.insert(name);
+    }
+
+    /// Adds records to the per-chromosome instance buffers, growing
+    /// them as needed.
+    pub fn append_records(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        records: impl IntoIterator<Item = GwasRecord>,
+    ) {
+        let mut new_instances: HashMap<String, Vec<PointInstance>> = HashMap::default();
+
+        for record in records {
+            let y = record.neg_log10_p as f32;
 
-            let pos = js_sys::Reflect::get(&value, &"ps".into()).unwrap();
-            let p = js_sys::Reflect::get(&value, &"p_wald".into()).unwrap();
+            self.min_neg_log10_p = self.min_neg_log10_p.min(y);
+            self.max_neg_log10_p = self.max_neg_log10_p.max(y);
 
-            let pos = pos.as_f64().unwrap();
-            let p = p.as_f64().unwrap();
+            if let Some(beta) = record.beta {
+                self.max_abs_beta = self.max_abs_beta.max(beta.abs() as f32);
+            }
 
-            min_p = min_p.min(p);
-            max_p = max_p.max(p);
+            if let Some(annotation) = &record.annotation {
+                self.add_category(annotation);
+            }
 
-            objects.entry(chr.clone()).or_default().push(value);
+            let instance = self.instance(&record);
+            new_instances
+                .entry(record.chr.clone())
+                .or_default()
+                .push(instance);
 
-            let vertices = vertex_datas.entry(chr).or_default();
+            let existing = self.data.get(&record.chr);
+            let ix = existing.map_or(0, |records| records.len());
 
-            let vertex = Vertex::new(pos as f32, p as f32);
-            vertices.push(vertex);
-            vertices.push(vertex);
-            vertices.push(vertex);
+            if existing.map_or(false, |records| ix > 0 && records.pos(ix - 1) > record.pos) {
+                self.unsorted.insert(record.chr.clone());
+            }
+
+            self.lod.entry(record.chr.clone()).or_default().insert(
+                ix,
+                record.pos,
+                record.neg_log10_p,
+            );
+
+            self.data
+                .entry(record.chr.clone())
+                .or_default()
+                .push(record);
         }
 
-        let mut vertex_buffers: HashMap<String, wgpu::Buffer> = HashMap::default();
-        let mut vertex_counts: HashMap<String, usize> = HashMap::default();
+        let instance_size = std::mem::size_of::<PointInstance>();
 
-        for chr in objects.keys() {
-            let vertex_data = vertex_datas.get(chr).unwrap();
+        for (chr, instances) in new_instances {
+            let count = self.instance_counts.get(&chr).copied().unwrap_or(0);
+            let capacity = self.instance_capacities.get(&chr).copied().unwrap_or(0);
 
-            let vertex_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("Vertices, chr {}", chr)),
-                contents: bytemuck::cast_slice(&vertex_data),
-                usage: wgpu::BufferUsage::VERTEX,
+            let new_count = count + instances.len();
+
+            if new_count <= capacity {
+                let instance_buf = self.instance_buffers.get(&chr).unwrap();
+                let offset = (count * instance_size) as wgpu::BufferAddress;
+
+                queue.write_buffer(instance_buf, offset, bytemuck::cast_slice(&instances));
+            } else {
+                // the old buffer can't be read back, so fill the new
+                // one from the records instead
+                let capacity = new_count.max(2 * capacity);
+                self.reupload_instances(device, queue, &chr, capacity);
+            }
+
+            self.instance_counts.insert(chr.clone(), new_count);
+            self.lod_dirty.insert(chr);
+        }
+    }
+
+    /// Replaces the instance buffer of `chr` with one holding all of
+    /// its records, with room for at least `capacity` instances.
+    fn reupload_instances(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        chr: &str,
+        capacity: usize,
+    ) {
+        let records = &self.data[chr];
+        let instances: Vec<PointInstance> = (0..records.len())
+            .map(|ix| self.instance_at(records, ix))
+            .collect();
+
+        let capacity = capacity.max(instances.len()).max(MIN_INSTANCE_CAPACITY);
+        let instance_size = std::mem::size_of::<PointInstance>();
+
+        let instance_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(&format!("Points, chr {}", chr)),
+            size: (capacity * instance_size) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::VERTEX | wgpu::BufferUsage::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        queue.write_buffer(&instance_buf, 0, bytemuck::cast_slice(&instances));
+
+        self.instance_buffers.insert(chr.to_string(), instance_buf);
+        self.instance_capacities.insert(chr.to_string(), capacity);
+        self.instance_counts
+            .insert(chr.to_string(), instances.len());
+    }
+
+    fn add_category(&mut self, annotation: &str) {
+        if self.categories.iter().any(|c| c == annotation) {
+            return;
+        }
+
+        if self.categories.len() < color::MAX_CATEGORIES {
+            self.categories.push(annotation.to_string());
+        } else {
+            self.other_categories = true;
+        }
+    }
+
+    fn instance(&self, record: &GwasRecord) -> PointInstance {
+        let value = match self.color_by {
+            ColorBy::Track | ColorBy::Chromosome => 0.0,
+            ColorBy::Annotation => match &record.annotation {
+                Some(annotation) => self
+                    .categories
+                    .iter()
+                    .position(|c| c == annotation)
+                    .unwrap_or(color::MAX_CATEGORIES) as f32,
+                None => std::f32::NAN,
+            },
+            by => by
+                .field_value(record)
+                .map(|value| value as f32)
+                .unwrap_or(std::f32::NAN),
+        };
+
+        PointInstance {
+            position: [record.pos as f32, record.neg_log10_p as f32],
+            value,
+            selected: 0.0,
+        }
+    }
+
+    /// The instance of the `ix`th of `records`, which for the columns
+    /// of a binary file are made without building the record.
+    fn instance_at(&self, records: &ChrRecords, ix: usize) -> PointInstance {
+        let chr = match records {
+            ChrRecords::Records(records) => return self.instance(&records[ix]),
+            ChrRecords::Columns(chr) => chr,
+        };
+
+        // the binary format holds nothing to colour the points by
+        let value = match self.color_by {
+            ColorBy::Track | ColorBy::Chromosome => 0.0,
+            _ => std::f32::NAN,
+        };
+
+        PointInstance {
+            position: [chr.positions[ix] as f32, chr.neg_log10_p.get(ix) as f32],
+            value,
+            selected: 0.0,
+        }
+    }
+
+    /// The `ix`th record of `chr`, if there is one.
+    pub fn record(&self, chr: &str, ix: usize) -> Option<Cow<'_, GwasRecord>> {
+        self.data.get(chr)?.get(ix)
+    }
+
+    /// Changes what the points are coloured by, which means
+    /// uploading all of them again.
+    pub fn set_color_by(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, by: ColorBy) {
+        if self.color_by == by {
+            return;
+        }
+
+        self.color_by = by;
+
+        let chrs: Vec<String> = self.data.keys().cloned().collect();
+
+        for chr in chrs {
+            let capacity = self.instance_capacities.get(&chr).copied().unwrap_or(0);
+            self.reupload_instances(device, queue, &chr, capacity);
+            self.lod_dirty.insert(chr);
+        }
+    }
+
+    /// The largest -log10(p) in the genome-wide range `start..end`,
+    /// given the genome-wide ranges of the chromosomes.
+    pub fn visible_max(
+        &self,
+        chr_ranges: &[(String, (usize, usize))],
+        (start, end): (f32, f32),
+    ) -> Option<f32> {
+        let start = start.max(0.0) as usize;
+        let end = end.max(0.0) as usize;
+
+        chr_ranges
+            .iter()
+            .filter(|(_, (chr_start, chr_end))| *chr_start < end && *chr_end > start)
+            .filter_map(|(chr, (chr_start, chr_end))| {
+                let local_start = start.saturating_sub(*chr_start);
+                let local_end = end.min(*chr_end) - chr_start;
+                self.lod.get(chr)?.max_in(local_start, local_end)
+            })
+            .fold(None, |max: Option<f64>, p| {
+                Some(max.map_or(p, |max| max.max(p)))
+            })
+            .map(|max| max as f32)
+    }
+
+    /// The number of records loaded.
+    pub fn record_count(&self) -> usize {
+        self.data.values().map(|records| records.len()).sum()
+    }
+
+    /// The range of values the colour map spans.
+    pub fn color_range(&self) -> (f32, f32) {
+        match self.color_by {
+            ColorBy::Beta if self.max_abs_beta > 0.0 => (-self.max_abs_beta, self.max_abs_beta),
+            ColorBy::Beta => (-1.0, 1.0),
+            _ => (0.0, 1.0),
+        }
+    }
+
+    /// Calls `f` with the records of the chromosome, the index and
+    /// clip space position of each record drawn in `track` at
+    /// `bp_per_pixel` within the genome-wide range `start..end`, along
+    /// with how many times larger than usual its marker is.
+    fn for_each_drawn<'a>(
+        &'a self,
+        chr_ranges: &[(String, (usize, usize))],
+        view: &View,
+        track: &TrackParams,
+        bp_per_pixel: f32,
+        (start, end): (f32, f32),
+        mut f: impl FnMut(&'a ChrRecords, usize, (f32, f32), f32),
+    ) {
+        let clip_per_bp = view.base_bp_width / view.scale;
+
+        let chrs = chr_ranges.iter().filter(|(_, (chr_start, chr_end))| {
+            (*chr_start as f32) < end && (*chr_end as f32) > start
+        });
+
+        for (chr, (chr_start, _)) in chrs {
+            let records = match self.data.get(chr) {
+                Some(records) => records,
+                None => continue,
+            };
+
+            let level = self.lod.get(chr).and_then(|pyramid| {
+                let ix = pyramid.level_for(bp_per_pixel)?;
+                pyramid.levels.get(ix)
             });
 
-            let vertex_count = vertex_data.len();
+            let drawn: Box<dyn Iterator<Item = usize>> = match level {
+                Some(level) => Box::new(level.records()),
+                None if self.unsorted.contains(chr) => Box::new(0..records.len()),
+                None => {
+                    let chr_start = *chr_start as f32;
+                    Box::new(records.indices_within(start - chr_start, end - chr_start))
+                }
+            };
+
+            // relative to the chromosome, as the points are drawn
+            let center = view.center - *chr_start as f32;
+
+            for ix in drawn {
+                if ix >= records.len() {
+                    continue;
+                }
+
+                let value = records.neg_log10_p(ix) as f32;
+
+                if value < track.min_y {
+                    continue;
+                }
 
-            vertex_buffers.insert(chr.to_owned(), vertex_buf);
-            vertex_counts.insert(chr.to_owned(), vertex_count);
+                let x = (records.pos(ix) as f32 - center) * clip_per_bp;
+                let y = track.y_of(value.min(track.max_y));
+
+                let scale = match track.highlight_above {
+                    Some(above) if value >= above => HIGHLIGHT_SCALE,
+                    _ => 1.0,
+                };
+
+                f(records, ix, (x, y), scale);
+            }
         }
+    }
 
-        Ok(Self {
-            vertex_buffers,
-            vertex_counts,
+    /// The record whose marker is drawn nearest to the clip space
+    /// point `(x, y)` in `track`, if any is within `radius`. Only the
+    /// records drawn at `bp_per_pixel` are considered, and the
+    /// distance to a highlighted marker is taken relative to its size.
+    pub fn nearest_record(
+        &self,
+        chr_ranges: &[(String, (usize, usize))],
+        view: &View,
+        track: &TrackParams,
+        bp_per_pixel: f32,
+        (x, y): (f32, f32),
+        radius: f32,
+    ) -> Option<RecordHit<'_>> {
+        let clip_per_bp = view.base_bp_width / view.scale;
+
+        let bp = view.center + x / clip_per_bp;
+        let bp_radius = HIGHLIGHT_SCALE * radius / clip_per_bp;
+        let range = (bp - bp_radius, bp + bp_radius);
+
+        let mut nearest: Option<(f32, &ChrRecords, usize)> = None;
+
+        self.for_each_drawn(
+            chr_ranges,
+            view,
+            track,
+            bp_per_pixel,
+            range,
+            |records, index, (px, py), scale| {
+                let (dx, dy) = (px - x, py - y);
+                let distance = (dx * dx + dy * dy).sqrt() / scale;
+
+                if distance <= radius && nearest.map_or(true, |(d, _, _)| distance < d) {
+                    nearest = Some((distance, records, index));
+                }
+            },
+        );
 
-            data: objects,
+        // only the record that's shown is built
+        let (distance, records, index) = nearest?;
 
-            min_p: min_p as f32,
-            max_p: max_p as f32,
+        Some(RecordHit {
+            distance,
+            index,
+            record: records.get(index)?,
         })
     }
+
+    /// The records drawn in `track` at `bp_per_pixel` inside the clip
+    /// space rectangle with the corners `a` and `b`, with their
+    /// indices.
+    pub fn records_in_rect(
+        &self,
+        chr_ranges: &[(String, (usize, usize))],
+        view: &View,
+        track: &TrackParams,
+        bp_per_pixel: f32,
+        a: (f32, f32),
+        b: (f32, f32),
+    ) -> Vec<(usize, Cow<'_, GwasRecord>)> {
+        let (x0, x1) = (a.0.min(b.0), a.0.max(b.0));
+        let (y0, y1) = (a.1.min(b.1), a.1.max(b.1));
+
+        let clip_per_bp = view.base_bp_width / view.scale;
+        let range = (
+            view.center + x0 / clip_per_bp,
+            view.center + x1 / clip_per_bp,
+        );
+
+        let mut inside = Vec::new();
+
+        self.for_each_drawn(
+            chr_ranges,
+            view,
+            track,
+            bp_per_pixel,
+            range,
+            |records, index, (x, y), _| {
+                if x0 <= x && x <= x1 && y0 <= y && y <= y1 {
+                    inside.extend(records.get(index).map(|record| (index, record)));
+                }
+            },
+        );
+
+        inside
+    }
+
+    /// Replaces the instances drawn as selected with those of the
+    /// given records, as their chromosomes and indices.
+    pub fn set_selection<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        selected: impl IntoIterator<Item = (&'a str, usize)>,
+    ) {
+        let mut instances: HashMap<&str, Vec<PointInstance>> = HashMap::default();
+
+        for (chr, ix) in selected {
+            if let Some(records) = self.data.get(chr).filter(|records| ix < records.len()) {
+                let mut instance = self.instance_at(records, ix);
+                instance.selected = 1.0;
+                instances.entry(chr).or_default().push(instance);
+            }
+        }
+
+        self.selection_buffers.clear();
+
+        for (chr, instances) in instances {
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(&format!("Selected points, chr {}", chr)),
+                contents: bytemuck::cast_slice(&instances),
+                usage: wgpu::BufferUsage::VERTEX,
+            });
+
+            self.selection_buffers
+                .insert(chr.to_string(), (buffer, instances.len()));
+        }
+    }
+
+    /// The index of the record drawn as the `instance`th point of
+    /// `chr` at `bp_per_pixel`, as `instances` draws them.
+    pub fn record_for_instance(
+        &self,
+        chr: &str,
+        bp_per_pixel: f32,
+        instance: usize,
+    ) -> Option<usize> {
+        let level = self
+            .lod
+            .get(chr)
+            .and_then(|pyramid| pyramid.level_for(bp_per_pixel))
+            .filter(|ix| self.lod_buffers.get(chr).map_or(false, |b| *ix < b.len()))
+            .and_then(|ix| self.lod.get(chr)?.levels.get(ix));
+
+        match level {
+            Some(level) => level.records().nth(instance),
+            None if instance < self.data.get(chr)?.len() => Some(instance),
+            None => None,
+        }
+    }
+
+    /// The instances of the selected records of `chr`, if any.
+    pub fn selected_instances(&self, chr: &str) -> Option<(wgpu::BufferSlice<'_>, usize)> {
+        let (buffer, count) = self.selection_buffers.get(chr)?;
+        Some((buffer.slice(..), *count))
+    }
+
+    /// What the legend shows for the dataset under `coloring`, or
+    /// `None` if it doesn't need one.
+    pub fn legend(&self, coloring: &Coloring) -> Option<Legend<'_>> {
+        if coloring.by == ColorBy::Annotation {
+            Some(Legend::Categories {
+                names: &self.categories,
+                other: self.other_categories,
+            })
+        } else if coloring.by.is_continuous() {
+            let (min, max) = self.color_range();
+            Some(Legend::Range(coloring.map, min, max))
+        } else {
+            None
+        }
+    }
+
+    /// Uploads the levels of detail of the chromosomes that have had
+    /// records added since the last call. The pyramids are updated as
+    /// records arrive, but uploading them is left to this, so that it
+    /// can be done once per frame rather than once per batch.
+    pub fn update_lod_buffers(&mut self, device: &wgpu::Device) {
+        let dirty: Vec<String> = self.lod_dirty.drain().collect();
+
+        for chr in dirty {
+            let pyramid = match self.lod.get(&chr) {
+                Some(pyramid) => pyramid,
+                None => continue,
+            };
+
+            let buffers = pyramid
+                .levels
+                .iter()
+                .map(|level| {
+                    let records = &self.data[&chr];
+                    let instances: Vec<PointInstance> = level
+                        .records()
+                        .map(|ix| self.instance_at(records, ix))
+                        .collect();
+
+                    let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some(&format!("Points, chr {}, {} bp bins", chr, level.bin_width)),
+                        contents: bytemuck::cast_slice(&instances),
+                        usage: wgpu::BufferUsage::VERTEX,
+                    });
+
+                    (buffer, instances.len())
+                })
+                .collect();
+
+            self.lod_buffers.insert(chr, buffers);
+        }
+    }
+
+    /// The instances to draw a chromosome with at a zoom level of
+    /// `bp_per_pixel`, and how many there are: the coarsest level of
+    /// detail that still has bins no wider than a pixel, or every
+    /// record when zoomed in further than the finest level.
+    pub fn instances(
+        &self,
+        chr: &str,
+        bp_per_pixel: f32,
+    ) -> Option<(wgpu::BufferSlice<'_>, usize)> {
+        let level = self
+            .lod
+            .get(chr)
+            .and_then(|pyramid| pyramid.level_for(bp_per_pixel))
+            .and_then(|ix| self.lod_buffers.get(chr)?.get(ix));
+
+        if let Some((buffer, count)) = level {
+            return Some((buffer.slice(..), *count));
+        }
+
+        let buffer = self.instance_buffers.get(chr)?;
+        let count = self.instance_counts.get(chr)?;
+
+        Some((buffer.slice(..), *count))
+    }
+}
+
+/// Fetches and parses `url`, handing the records to `on_records` in
+/// batches as the response body arrives, so that they can be shown
+/// before the whole file has loaded. Tables and JSON arrays are
+/// parsed as they arrive; the binary format is read once the whole
+/// body has loaded.
+///
+/// Batches are handed over before the rest of the file has been
+/// read, so if this returns an error, those already handed over are
+/// only part of the dataset and should be discarded. Rows that can't
+/// be read only cause an error if `options` doesn't allow skipping
+/// them.
+pub async fn stream_records(
+    url: &str,
+    format: GwasFormat,
+    options: LoadOptions,
+    mut on_records: impl FnMut(RecordBatch),
+) -> Result<LoadSummary, LoadError> {
+    let resp = fetch(url).await?;
+
+    // the columns are handed over as they are, one chromosome per
+    // batch, rather than as records
+    if format == GwasFormat::Binary {
+        let bytes = response_bytes(resp).await?;
+
+        for chr in binary::decode(&bytes)? {
+            on_records(RecordBatch::Columns(chr.into_owned()));
+        }
+
+        return Ok(LoadSummary::default());
+    }
+
+    let mut body = BodyReader::new(&resp).ok_or_else(|| LoadError::Network {
+        url: url.to_string(),
+        message: "response has no body".into(),
+    })?;
+
+    let mut decoder = StreamDecoder::default();
+
+    let skipped_rows = match format.table_layout() {
+        Some(layout) => {
+            let mut reader = TableReader::new(layout).skip_bad_rows(options.skip_bad_rows);
+
+            while let Some(chunk) = body.next_chunk().await? {
+                let records = reader.push(&decoder.push(&chunk)?)?;
+
+                if !records.is_empty() {
+                    on_records(RecordBatch::Records(records));
+                }
+            }
+
+            let mut records = reader.push(&decoder.finish()?)?;
+            records.extend(reader.finish()?);
+            on_records(RecordBatch::Records(records));
+
+            reader.skipped_rows()
+        }
+        None => {
+            let mut reader = JsonReader::new().skip_bad_rows(options.skip_bad_rows);
+
+            while let Some(chunk) = body.next_chunk().await? {
+                let records = reader.push(&decoder.push(&chunk)?)?;
+
+                if !records.is_empty() {
+                    on_records(RecordBatch::Records(records));
+                }
+            }
+
+            let records = reader.push(&decoder.finish()?)?;
+            reader.finish()?;
+            on_records(RecordBatch::Records(records));
+
+            reader.skipped_rows()
+        }
+    };
+
+    let mut assembly = None;
+
+    // the sidecar is optional, so failing to load it isn't an error
+    if format == GwasFormat::Ssf {
+        let sidecar_url = format!("{}{}", url, ssf::SIDECAR_SUFFIX);
+
+        if let Ok(sidecar) = fetch(&sidecar_url).await {
+            if let Ok(text) = response_text(sidecar).await {
+                let metadata = SsfMetadata::parse_yaml(&text);
+                assembly = metadata.genome_assembly().map(String::from);
+            }
+        }
+    }
+
+    Ok(LoadSummary {
+        assembly,
+        skipped_rows,
+    })
+}
+
+/// Parses a whole file at once. `bytes` must already be
+/// decompressed.
+pub fn parse_bytes(bytes: &[u8], format: &GwasFormat) -> Result<Vec<GwasRecord>, LoadError> {
+    let (records, _) = parse_bytes_with(bytes, format, LoadOptions::default())?;
+    Ok(records)
+}
+
+/// Like `parse_bytes`, but also returns the number of rows skipped.
+fn parse_bytes_with(
+    bytes: &[u8],
+    format: &GwasFormat,
+    options: LoadOptions,
+) -> Result<(Vec<GwasRecord>, usize), LoadError> {
+    let text = || {
+        std::str::from_utf8(bytes).map_err(|_| LoadError::Parse("file is not valid UTF-8".into()))
+    };
+
+    match format.table_layout() {
+        Some(layout) => {
+            let mut reader = TableReader::new(layout).skip_bad_rows(options.skip_bad_rows);
+
+            let mut records = reader.push(text()?.as_bytes())?;
+            records.extend(reader.finish()?);
+
+            Ok((records, reader.skipped_rows()))
+        }
+        None if *format == GwasFormat::Binary => {
+            let chrs = binary::decode(bytes)?;
+            Ok((chrs.iter().flat_map(|chr| chr.records()).collect(), 0))
+        }
+        None => {
+            let mut reader = JsonReader::new().skip_bad_rows(options.skip_bad_rows);
+
+            let records = reader.push(text()?.as_bytes())?;
+            reader.finish()?;
+
+            Ok((records, reader.skipped_rows()))
+        }
+    }
+}
+
+/// Converts a file in any of the supported formats to the compact
+/// binary format, decompressing it first if needed.
+pub fn to_binary(
+    bytes: Vec<u8>,
+    format: &GwasFormat,
+    precision: binary::Precision,
+) -> Result<Vec<u8>, LoadError> {
+    let bytes = crate::fetch::decompress(bytes)?;
+    let records = parse_bytes(&bytes, format)?;
+    binary::encode(&records, precision)
+}
+
+/// Incrementally parses a JSON array of records from chunks of
+/// bytes, such as those of a streamed response body.
+pub struct JsonReader {
+    splitter: JsonArraySplitter,
+
+    skip_bad_rows: bool,
+    skipped_rows: usize,
+}
+
+impl JsonReader {
+    pub fn new() -> Self {
+        Self {
+            splitter: JsonArraySplitter::default(),
+
+            skip_bad_rows: false,
+            skipped_rows: 0,
+        }
+    }
+
+    /// If set, records with missing or invalid fields are skipped and
+    /// counted rather than failing the whole array.
+    pub fn skip_bad_rows(mut self, skip: bool) -> Self {
+        self.skip_bad_rows = skip;
+        self
+    }
+
+    /// The number of records skipped so far.
+    pub fn skipped_rows(&self) -> usize {
+        self.skipped_rows
+    }
+
+    /// Parses all complete records available after appending `bytes`.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<GwasRecord>, LoadError> {
+        let first_row = self.splitter.elements() + 1;
+
+        let batch = match self.splitter.push(bytes)? {
+            Some(batch) => batch,
+            None => return Ok(Vec::new()),
+        };
+
+        let json = js_sys::JSON::parse(&batch)
+            .map_err(|err| LoadError::Parse(format!("error parsing JSON: {}", js_message(&err))))?;
+
+        // the splitter always hands over an array
+        let json_array: js_sys::Array = json.unchecked_into();
+
+        let mut records = Vec::with_capacity(json_array.length() as usize);
+
+        for (ix, value) in json_array.iter().enumerate() {
+            match record_from_js(first_row + ix, &value) {
+                Ok(record) => records.push(record),
+                Err(err) if self.skip_bad_rows && err.is_row_error() => self.skipped_rows += 1,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(records)
+    }
+
+    /// Checks that the array was complete once there are no more
+    /// bytes.
+    pub fn finish(&mut self) -> Result<(), LoadError> {
+        self.splitter.finish()
+    }
+}
+
+impl Default for JsonReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reads a record from a JSON object using GEMMA's field names, or
+/// a precomputed `log10p` field holding -log10(p) in place of
+/// `p_wald`, as well as optional `annotation` and `ld_r2` fields.
+/// `row` is the record's position in the array, from 1.
+fn record_from_js(row: usize, value: &JsValue) -> Result<GwasRecord, LoadError> {
+    let get = |key: &str| js_sys::Reflect::get(value, &key.into()).ok();
+
+    let get_string = |key: &str| get(key).and_then(|v| v.as_string());
+    let get_f64 = |key: &str| get(key).and_then(|v| v.as_f64());
+
+    let missing = |field: &str| LoadError::MissingField {
+        row,
+        field: field.to_string(),
+    };
+
+    let chr = get_string("chr").ok_or_else(|| missing("chr"))?;
+    let pos = get_f64("ps").ok_or_else(|| missing("ps"))?;
+
+    let neg_log10_p = match get_f64("p_wald") {
+        Some(p) => record::neg_log10_p_value(p).ok_or_else(|| LoadError::InvalidValue {
+            row,
+            field: "p_wald".into(),
+            value: p.to_string(),
+        })?,
+        None => get_f64("log10p").ok_or_else(|| missing("p_wald"))?,
+    };
+
+    let mut record = GwasRecord::new(&chr, pos as usize, neg_log10_p);
+
+    record.rs = get_string("rs");
+    record.allele1 = get_string("allele1");
+    record.allele0 = get_string("allele0");
+
+    record.af = get_f64("af");
+    record.beta = get_f64("beta");
+    record.se = get_f64("se");
+
+    record.annotation = get_string("annotation");
+    record.ld_r2 = get_f64("ld_r2");
+
+    Ok(record)
 }
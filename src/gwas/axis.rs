@@ -0,0 +1,144 @@
+//! The -log10(p) axis of the tracks: how far it reaches, and where
+//! its ticks go.
+
+/// The number of ticks an axis aims for.
+const TARGET_TICKS: usize = 5;
+
+/// The share of a track's height that the part of a compressed axis
+/// above its cutoff is squeezed into.
+pub const COMPRESSED_FRACTION: f32 = 0.25;
+
+/// How the far end of each track's axis is chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum YAxisMode {
+    /// Fit to the largest value in view, rounded up to a tick.
+    Auto,
+    /// Always the given -log10(p); larger values are drawn at the end
+    /// of the axis.
+    Fixed(f32),
+    /// Fit to the largest value of the dataset, but no further than
+    /// the given -log10(p); larger values are drawn at the end of the
+    /// axis.
+    Capped(f32),
+}
+
+impl Default for YAxisMode {
+    fn default() -> Self {
+        YAxisMode::Auto
+    }
+}
+
+impl YAxisMode {
+    /// Parses `auto`, `fixed:<max>` or `cap:<max>`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_ascii_lowercase();
+
+        if text == "auto" {
+            return Some(YAxisMode::Auto);
+        }
+
+        let (mode, max) = text.split_once(':')?;
+        let max: f32 = max.trim().parse().ok()?;
+
+        if !(max > 0.0 && max.is_finite()) {
+            return None;
+        }
+
+        match mode {
+            "fixed" => Some(YAxisMode::Fixed(max)),
+            "cap" | "capped" => Some(YAxisMode::Capped(max)),
+            _ => None,
+        }
+    }
+
+    /// The -log10(p) at the far end of a track's axis, given the
+    /// largest value of its dataset and the largest one in view, and
+    /// whether larger values are truncated to it.
+    pub fn max_y(&self, data_max: f32, visible_max: Option<f32>) -> (f32, bool) {
+        match *self {
+            YAxisMode::Auto => (nice_ceil(visible_max.unwrap_or(data_max)), false),
+            YAxisMode::Fixed(max) => (max, data_max > max),
+            YAxisMode::Capped(cap) if data_max > cap => (cap, true),
+            YAxisMode::Capped(_) => (nice_ceil(data_max), false),
+        }
+    }
+}
+
+/// The distance between the ticks of an axis spanning `span`: 1, 2
+/// or 5 times a power of ten.
+pub fn tick_step(span: f32) -> f32 {
+    let raw = span / TARGET_TICKS as f32;
+
+    if !(raw > 0.0 && raw.is_finite()) {
+        return 1.0;
+    }
+
+    let magnitude = 10f32.powf(raw.log10().floor());
+    let normalized = raw / magnitude;
+
+    let step = if normalized <= 1.0 {
+        1.0
+    } else if normalized <= 2.0 {
+        2.0
+    } else if normalized <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+
+    step * magnitude
+}
+
+/// `value` rounded up to a tick of an axis reaching it, or 1 if
+/// there's nothing to fit.
+pub fn nice_ceil(value: f32) -> f32 {
+    if !(value > 0.0 && value.is_finite()) {
+        return 1.0;
+    }
+
+    let step = tick_step(value);
+    (value / step).ceil() * step
+}
+
+/// The ticks of an axis from `min` to `max`, inclusive.
+pub fn ticks(min: f32, max: f32) -> Vec<f32> {
+    let step = tick_step(max - min);
+    let first = (min / step - 1e-3).ceil() as i64;
+    let last = (max / step + 1e-3).floor() as i64;
+
+    (first..=last).map(|ix| ix as f32 * step).collect()
+}
+
+/// How much the part of an axis from `min` to `max` above `cutoff`
+/// is scaled down by so that it takes up `COMPRESSED_FRACTION` of
+/// the track, or 1 if it already takes up no more than that.
+pub fn compression_scale(min: f32, max: f32, cutoff: f32) -> f32 {
+    if !(min < cutoff && cutoff < max) {
+        return 1.0;
+    }
+
+    let below = cutoff - min;
+    let above = max - cutoff;
+
+    let scale = COMPRESSED_FRACTION * below / ((1.0 - COMPRESSED_FRACTION) * above);
+    scale.min(1.0)
+}
+
+/// The position of `value` along an axis whose part above `cutoff`
+/// is scaled by `scale`, in -log10(p) units below the cutoff.
+pub fn compress(value: f32, cutoff: f32, scale: f32) -> f32 {
+    if value > cutoff {
+        cutoff + (value - cutoff) * scale
+    } else {
+        value
+    }
+}
+
+/// The inverse of `compress`.
+pub fn decompress(units: f32, cutoff: f32, scale: f32) -> f32 {
+    if units > cutoff && scale > 0.0 {
+        cutoff + (units - cutoff) / scale
+    } else {
+        units
+    }
+}
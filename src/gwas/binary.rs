@@ -0,0 +1,327 @@
+//! A compact binary format for summary statistics, which can be
+//! loaded without parsing any text.
+//!
+//! All integers and floats are little-endian. The file starts with
+//! the magic bytes `GWB1`, a `u32` of flags and a `u32` chromosome
+//! count, followed by one block per chromosome:
+//!
+//! - `u32` name length, then the UTF-8 name
+//! - `u32` record count `n`
+//! - `n` `u32` positions, sorted in ascending order
+//! - `n` -log10(p) values, `f64` if `FLAG_F64` is set, else `f32`
+//! - if `FLAG_RSIDS` is set, `n + 1` `u32` offsets into the string
+//!   bytes that follow, then the bytes of all rsIDs concatenated
+//!
+//! Every column and block starts at a multiple of 8 bytes from the
+//! start of the file, so the columns can be used in place.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use bytemuck::Pod;
+
+use crate::error::LoadError;
+
+use super::record::GwasRecord;
+
+type Result<T> = std::result::Result<T, LoadError>;
+
+pub const MAGIC: &[u8; 4] = b"GWB1";
+
+const FLAG_F64: u32 = 1;
+const FLAG_RSIDS: u32 = 1 << 1;
+
+/// The float type the -log10(p) column is stored as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    F64,
+}
+
+/// The column of -log10(p) values of a chromosome.
+#[derive(Debug, Clone)]
+pub enum NegLog10Column<'a> {
+    F32(Cow<'a, [f32]>),
+    F64(Cow<'a, [f64]>),
+}
+
+impl NegLog10Column<'_> {
+    pub fn get(&self, ix: usize) -> f64 {
+        match self {
+            NegLog10Column::F32(col) => col[ix] as f64,
+            NegLog10Column::F64(col) => col[ix],
+        }
+    }
+
+    pub fn into_owned(self) -> NegLog10Column<'static> {
+        match self {
+            NegLog10Column::F32(col) => NegLog10Column::F32(Cow::Owned(col.into_owned())),
+            NegLog10Column::F64(col) => NegLog10Column::F64(Cow::Owned(col.into_owned())),
+        }
+    }
+}
+
+/// The records of one chromosome, borrowing from the encoded bytes
+/// where possible.
+#[derive(Debug, Clone)]
+pub struct BinaryChr<'a> {
+    pub name: Cow<'a, str>,
+    pub positions: Cow<'a, [u32]>,
+    pub neg_log10_p: NegLog10Column<'a>,
+
+    rsid_offsets: Option<Cow<'a, [u32]>>,
+    rsid_bytes: Cow<'a, [u8]>,
+}
+
+impl<'a> BinaryChr<'a> {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Copies the columns out of the encoded bytes, so that they can
+    /// outlive them.
+    pub fn into_owned(self) -> BinaryChr<'static> {
+        BinaryChr {
+            name: Cow::Owned(self.name.into_owned()),
+            positions: Cow::Owned(self.positions.into_owned()),
+            neg_log10_p: self.neg_log10_p.into_owned(),
+
+            rsid_offsets: self
+                .rsid_offsets
+                .map(|offsets| Cow::Owned(offsets.into_owned())),
+            rsid_bytes: Cow::Owned(self.rsid_bytes.into_owned()),
+        }
+    }
+
+    pub fn rsid(&self, ix: usize) -> Option<&str> {
+        let offsets = self.rsid_offsets.as_ref()?;
+        let start = offsets[ix] as usize;
+        let end = offsets[ix + 1] as usize;
+
+        let rsid = std::str::from_utf8(self.rsid_bytes.get(start..end)?).ok()?;
+
+        if rsid.is_empty() {
+            None
+        } else {
+            Some(rsid)
+        }
+    }
+
+    /// The `ix`th record, built from the columns.
+    pub fn record(&self, ix: usize) -> GwasRecord {
+        let pos = self.positions[ix] as usize;
+        let mut record = GwasRecord::new(&self.name, pos, self.neg_log10_p.get(ix));
+        record.rs = self.rsid(ix).map(String::from);
+        record
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = GwasRecord> + '_ {
+        (0..self.len()).map(move |ix| self.record(ix))
+    }
+}
+
+/// Encodes records in the binary format. Chromosomes are stored in
+/// the order they first appear in, and each one's records are sorted
+/// by position.
+///
+/// Positions are stored as `u32`s, so a record whose position
+/// doesn't fit is an error.
+pub fn encode(records: &[GwasRecord], precision: Precision) -> Result<Vec<u8>> {
+    let mut chrs: Vec<(&str, Vec<(u32, &GwasRecord)>)> = Vec::new();
+    let mut chr_ixs: HashMap<&str, usize> = HashMap::new();
+
+    for (ix, record) in records.iter().enumerate() {
+        let pos = u32::try_from(record.pos).map_err(|_| LoadError::InvalidValue {
+            row: ix + 1,
+            field: "pos".into(),
+            value: record.pos.to_string(),
+        })?;
+
+        let chr_ix = *chr_ixs.entry(&record.chr).or_insert_with(|| {
+            chrs.push((&record.chr, Vec::new()));
+            chrs.len() - 1
+        });
+
+        chrs[chr_ix].1.push((pos, record));
+    }
+
+    let has_rsids = records.iter().any(|r| r.rs.is_some());
+
+    let mut flags = 0;
+    if precision == Precision::F64 {
+        flags |= FLAG_F64;
+    }
+    if has_rsids {
+        flags |= FLAG_RSIDS;
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&(chrs.len() as u32).to_le_bytes());
+
+    for (name, mut chr_records) in chrs {
+        chr_records.sort_by_key(|(pos, _)| *pos);
+
+        pad_to_8(&mut out);
+        out.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        out.extend_from_slice(name.as_bytes());
+
+        pad_to_8(&mut out);
+        out.extend_from_slice(&(chr_records.len() as u32).to_le_bytes());
+
+        pad_to_8(&mut out);
+        for (pos, _) in chr_records.iter() {
+            out.extend_from_slice(&pos.to_le_bytes());
+        }
+
+        pad_to_8(&mut out);
+        for (_, record) in chr_records.iter() {
+            match precision {
+                Precision::F32 => out.extend_from_slice(&(record.neg_log10_p as f32).to_le_bytes()),
+                Precision::F64 => out.extend_from_slice(&record.neg_log10_p.to_le_bytes()),
+            }
+        }
+
+        if has_rsids {
+            pad_to_8(&mut out);
+
+            let mut offset = 0u32;
+            out.extend_from_slice(&offset.to_le_bytes());
+
+            for (_, record) in chr_records.iter() {
+                offset += record.rs.as_ref().map(|rs| rs.len()).unwrap_or(0) as u32;
+                out.extend_from_slice(&offset.to_le_bytes());
+            }
+
+            for (_, record) in chr_records.iter() {
+                if let Some(rs) = &record.rs {
+                    out.extend_from_slice(rs.as_bytes());
+                }
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Decodes the binary format, borrowing the columns from `bytes`
+/// when they're suitably aligned in memory.
+pub fn decode(bytes: &[u8]) -> Result<Vec<BinaryChr<'_>>> {
+    if !bytes.starts_with(MAGIC) {
+        return Err(LoadError::Parse(
+            "not a binary summary statistics file".into(),
+        ));
+    }
+
+    let mut cursor = Cursor { bytes, offset: 4 };
+
+    let flags = cursor.u32()?;
+    let chr_count = cursor.u32()?;
+
+    // the count isn't trusted to size the vector with, as a corrupt
+    // file could claim billions of chromosomes
+    let mut chrs = Vec::new();
+
+    for _ in 0..chr_count {
+        cursor.align_to_8();
+        let name_len = cursor.u32()? as usize;
+        let name = std::str::from_utf8(cursor.take(name_len)?)
+            .map_err(|_| LoadError::Parse("chromosome name is not valid UTF-8".into()))?;
+
+        cursor.align_to_8();
+        let count = cursor.u32()? as usize;
+
+        cursor.align_to_8();
+        let positions = cursor.column::<u32>(count)?;
+
+        cursor.align_to_8();
+        let neg_log10_p = if flags & FLAG_F64 != 0 {
+            NegLog10Column::F64(cursor.column::<f64>(count)?)
+        } else {
+            NegLog10Column::F32(cursor.column::<f32>(count)?)
+        };
+
+        let (rsid_offsets, rsid_bytes) = if flags & FLAG_RSIDS != 0 {
+            cursor.align_to_8();
+            let offsets = cursor.column::<u32>(count.checked_add(1).ok_or_else(truncated)?)?;
+            let len = offsets.last().copied().unwrap_or(0) as usize;
+            (Some(offsets), Cow::Borrowed(cursor.take(len)?))
+        } else {
+            (None, Cow::Borrowed(&[][..]))
+        };
+
+        chrs.push(BinaryChr {
+            name: Cow::Borrowed(name),
+            positions,
+            neg_log10_p,
+
+            rsid_offsets,
+            rsid_bytes,
+        });
+    }
+
+    Ok(chrs)
+}
+
+/// The error for lengths and counts that run past the end of the
+/// file, including ones too large to even add up.
+fn truncated() -> LoadError {
+    LoadError::Parse("binary summary statistics file is truncated".into())
+}
+
+fn pad_to_8(out: &mut Vec<u8>) {
+    while out.len() % 8 != 0 {
+        out.push(0);
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes: &'a [u8] = self.bytes;
+        let end = self.offset.checked_add(len).ok_or_else(truncated)?;
+        let slice = bytes.get(self.offset..end).ok_or_else(truncated)?;
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn align_to_8(&mut self) {
+        self.offset = (self.offset + 7) / 8 * 8;
+    }
+
+    /// Reads `count` values, in place if the bytes happen to be
+    /// aligned for `T`, and copied otherwise.
+    ///
+    /// This assumes a little-endian target, which wasm always is.
+    fn column<T: Pod>(&mut self, count: usize) -> Result<Cow<'a, [T]>> {
+        let len = count
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or_else(truncated)?;
+        let bytes = self.take(len)?;
+
+        match bytemuck::try_cast_slice(bytes) {
+            Ok(column) => Ok(Cow::Borrowed(column)),
+            Err(_) => {
+                let mut column = vec![T::zeroed(); count];
+                bytemuck::cast_slice_mut(&mut column).copy_from_slice(bytes);
+                Ok(Cow::Owned(column))
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! How the points of a dataset are coloured.
+//!
+//! Colouring by track or chromosome only needs the per-chromosome
+//! uniform colour. The other modes give each point a value, either
+//! the index of its annotation's category or a numeric field, which
+//! the vertex shader turns into a colour using the palette in the
+//! uniform block.
+
+use super::record::GwasRecord;
+
+/// The most categories of an annotation that get a colour of their
+/// own; any further ones share `OTHER_COLOR`.
+pub const MAX_CATEGORIES: usize = 7;
+
+/// ColorBrewer's Set1.
+pub const CATEGORY_COLORS: [[f32; 4]; MAX_CATEGORIES] = [
+    [0.894, 0.102, 0.11, 1.0],  // #e41a1c
+    [0.216, 0.494, 0.722, 1.0], // #377eb8
+    [0.302, 0.686, 0.29, 1.0],  // #4daf4a
+    [0.596, 0.306, 0.639, 1.0], // #984ea3
+    [1.0, 0.498, 0.0, 1.0],     // #ff7f00
+    [1.0, 1.0, 0.2, 1.0],       // #ffff33
+    [0.651, 0.337, 0.157, 1.0], // #a65628
+];
+
+/// The colour of categories past `MAX_CATEGORIES`, and of points
+/// lacking the value they're coloured by.
+pub const OTHER_COLOR: [f32; 4] = [0.6, 0.6, 0.6, 1.0];
+
+/// The number of colours the uniform block has room for.
+const PALETTE_LEN: usize = 8;
+
+/// What the colour of a point depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorBy {
+    /// Every point in the colour of its track.
+    Track,
+    /// Alternating between the track colour and a lighter shade of
+    /// it, chromosome by chromosome.
+    Chromosome,
+    /// The record's annotation, e.g. the variant's consequence.
+    Annotation,
+    Beta,
+    Af,
+    LdR2,
+}
+
+impl ColorBy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "track" => Some(ColorBy::Track),
+            "chromosome" | "chr" => Some(ColorBy::Chromosome),
+            "annotation" => Some(ColorBy::Annotation),
+            "beta" => Some(ColorBy::Beta),
+            "af" => Some(ColorBy::Af),
+            "ld_r2" | "r2" => Some(ColorBy::LdR2),
+            _ => None,
+        }
+    }
+
+    /// Whether points are coloured through a colour map.
+    pub fn is_continuous(&self) -> bool {
+        matches!(self, ColorBy::Beta | ColorBy::Af | ColorBy::LdR2)
+    }
+
+    /// The field a continuous colouring maps to colours.
+    pub fn field_value(&self, record: &GwasRecord) -> Option<f64> {
+        match self {
+            ColorBy::Beta => record.beta,
+            ColorBy::Af => record.af,
+            ColorBy::LdR2 => record.ld_r2,
+            _ => None,
+        }
+    }
+}
+
+/// The colour maps continuous values can be shown with, as evenly
+/// spaced stops that are linearly interpolated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMap {
+    Viridis,
+    Magma,
+    /// ColorBrewer's diverging red-blue, for signed values.
+    RdBu,
+}
+
+impl ColorMap {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "viridis" => Some(ColorMap::Viridis),
+            "magma" => Some(ColorMap::Magma),
+            "rdbu" => Some(ColorMap::RdBu),
+            _ => None,
+        }
+    }
+
+    pub fn stops(&self) -> &'static [[f32; 4]] {
+        match self {
+            ColorMap::Viridis => &[
+                [0.267, 0.004, 0.329, 1.0], // #440154
+                [0.231, 0.322, 0.545, 1.0], // #3b528b
+                [0.129, 0.569, 0.549, 1.0], // #21918c
+                [0.369, 0.788, 0.384, 1.0], // #5ec962
+                [0.992, 0.906, 0.145, 1.0], // #fde725
+            ],
+            ColorMap::Magma => &[
+                [0.0, 0.0, 0.016, 1.0],      // #000004
+                [0.3176, 0.071, 0.486, 1.0], // #51127c
+                [0.718, 0.216, 0.475, 1.0],  // #b73779
+                [0.988, 0.537, 0.38, 1.0],   // #fc8961
+                [0.988, 0.992, 0.749, 1.0],  // #fcfdbf
+            ],
+            ColorMap::RdBu => &[
+                [0.129, 0.4, 0.675, 1.0],   // #2166ac
+                [0.573, 0.773, 0.871, 1.0], // #92c5de
+                [0.969, 0.969, 0.969, 1.0], // #f7f7f7
+                [0.957, 0.647, 0.51, 1.0],  // #f4a582
+                [0.698, 0.094, 0.169, 1.0], // #b2182b
+            ],
+        }
+    }
+
+    /// The colour at `t` in [0, 1], as the shader computes it.
+    pub fn sample(&self, t: f32) -> [f32; 4] {
+        let stops = self.stops();
+
+        let x = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let ix = (x.floor() as usize).min(stops.len() - 2);
+        let frac = x - ix as f32;
+
+        let mut color = stops[ix];
+        for (c, next) in color.iter_mut().zip(stops[ix + 1].iter()) {
+            *c += (next - *c) * frac;
+        }
+        color
+    }
+}
+
+/// How the points of all datasets are coloured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Coloring {
+    pub by: ColorBy,
+    /// Only used for continuous colourings.
+    pub map: ColorMap,
+}
+
+impl Default for Coloring {
+    fn default() -> Self {
+        Self::new(ColorBy::Chromosome)
+    }
+}
+
+impl Coloring {
+    /// Colouring by `by` with the map that suits it: diverging for
+    /// effect sizes, which are signed, and sequential otherwise.
+    pub fn new(by: ColorBy) -> Self {
+        let map = match by {
+            ColorBy::Beta => ColorMap::RdBu,
+            _ => ColorMap::Viridis,
+        };
+
+        Self { by, map }
+    }
+
+    /// The colour of the points of the `chr_ix`th chromosome of a
+    /// track, when they don't have colours of their own.
+    pub fn chr_color(&self, track_color: [f32; 4], chr_ix: usize) -> [f32; 4] {
+        if self.by == ColorBy::Chromosome && chr_ix % 2 == 1 {
+            let [r, g, b, a] = track_color;
+            let lighten = |c: f32| c + (1.0 - c) * 0.5;
+            [lighten(r), lighten(g), lighten(b), a]
+        } else {
+            track_color
+        }
+    }
+
+    /// The part of the uniform block the shader colours the points
+    /// with: the mode, the number of palette colours and the range of
+    /// the continuous values, followed by the palette.
+    pub fn uniform_data(&self, range: (f32, f32)) -> [f32; 4 + 4 * PALETTE_LEN] {
+        let mut data = [0.0; 4 + 4 * PALETTE_LEN];
+
+        let (mode, palette): (f32, Vec<[f32; 4]>) = match self.by {
+            ColorBy::Track | ColorBy::Chromosome => (0.0, Vec::new()),
+            ColorBy::Annotation => {
+                let mut palette = CATEGORY_COLORS.to_vec();
+                palette.push(OTHER_COLOR);
+                (1.0, palette)
+            }
+            _ => (2.0, self.map.stops().to_vec()),
+        };
+
+        data[0] = mode;
+        data[1] = palette.len() as f32;
+        data[2] = range.0;
+        data[3] = range.1;
+
+        for (ix, color) in palette.iter().enumerate() {
+            data[4 + 4 * ix..8 + 4 * ix].copy_from_slice(color);
+        }
+
+        data
+    }
+}
+
+/// What a dataset's legend shows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Legend<'a> {
+    /// The categories with colours of their own, and whether there
+    /// are more that are shown as `OTHER_COLOR`.
+    Categories { names: &'a [String], other: bool },
+    /// The range of values the colour map spans.
+    Range(ColorMap, f32, f32),
+}
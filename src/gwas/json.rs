@@ -0,0 +1,168 @@
+use crate::error::LoadError;
+
+type Result<T> = std::result::Result<T, LoadError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the opening bracket.
+    Start,
+    /// Between elements of the array.
+    Between,
+    /// Inside an element.
+    Element,
+    /// After the closing bracket.
+    End,
+}
+
+/// Splits a JSON array into batches of its complete elements as the
+/// bytes of the array arrive, so that the elements can be parsed
+/// before the rest of the array has loaded.
+///
+/// Only the structure of the array is checked here; the elements
+/// themselves are left to a JSON parser. Each batch is itself a JSON
+/// array, holding the elements completed by one chunk.
+pub struct JsonArraySplitter {
+    state: State,
+
+    element: Vec<u8>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+
+    elements: usize,
+}
+
+impl Default for JsonArraySplitter {
+    fn default() -> Self {
+        Self {
+            state: State::Start,
+
+            element: Vec::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+
+            elements: 0,
+        }
+    }
+}
+
+impl JsonArraySplitter {
+    /// The number of elements completed so far.
+    pub fn elements(&self) -> usize {
+        self.elements
+    }
+
+    /// Appends `bytes`, returning the elements they complete as a JSON
+    /// array, if there are any.
+    ///
+    /// Elements may be split across chunks, even in the middle of a
+    /// multibyte character, as they are only cut at ASCII brackets,
+    /// quotes and commas.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Option<String>> {
+        let mut batch = vec![b'['];
+
+        for &byte in bytes {
+            match self.state {
+                State::Start if byte.is_ascii_whitespace() => (),
+                State::Start if byte == b'[' => self.state = State::Between,
+                State::Start => {
+                    return Err(LoadError::Parse("expected a JSON array of records".into()))
+                }
+
+                State::Between if byte.is_ascii_whitespace() || byte == b',' => (),
+                State::Between if byte == b']' => self.state = State::End,
+                State::Between => {
+                    self.state = State::Element;
+                    self.element_byte(byte, &mut batch);
+                }
+
+                State::Element => self.element_byte(byte, &mut batch),
+
+                State::End if byte.is_ascii_whitespace() => (),
+                State::End => {
+                    return Err(LoadError::Parse("trailing data after JSON array".into()))
+                }
+            }
+        }
+
+        if batch.len() == 1 {
+            return Ok(None);
+        }
+
+        batch.push(b']');
+
+        // elements only end at ASCII bytes, so are whole characters
+        String::from_utf8(batch)
+            .map(Some)
+            .map_err(|_| LoadError::Parse("file is not valid UTF-8".into()))
+    }
+
+    /// Checks that the array was closed once there are no more bytes.
+    pub fn finish(&mut self) -> Result<()> {
+        match self.state {
+            State::End => Ok(()),
+            State::Start => Err(LoadError::Parse("expected a JSON array of records".into())),
+            State::Between | State::Element => {
+                Err(LoadError::Parse("JSON array is truncated".into()))
+            }
+        }
+    }
+
+    fn element_byte(&mut self, byte: u8, batch: &mut Vec<u8>) {
+        // numbers and literals have no closing delimiter, so end at
+        // whatever follows them
+        let in_scalar = self.depth == 0 && !self.in_string && !self.element.is_empty();
+
+        if in_scalar && (byte == b',' || byte == b']' || byte.is_ascii_whitespace()) {
+            self.complete(batch);
+
+            if byte == b']' {
+                self.state = State::End;
+            }
+
+            return;
+        }
+
+        self.element.push(byte);
+
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if byte == b'\\' {
+                self.escaped = true;
+            } else if byte == b'"' {
+                self.in_string = false;
+
+                if self.depth == 0 {
+                    self.complete(batch);
+                }
+            }
+
+            return;
+        }
+
+        match byte {
+            b'"' => self.in_string = true,
+            b'{' | b'[' => self.depth += 1,
+            b'}' | b']' => {
+                self.depth = self.depth.saturating_sub(1);
+
+                if self.depth == 0 {
+                    self.complete(batch);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn complete(&mut self, batch: &mut Vec<u8>) {
+        if batch.len() > 1 {
+            batch.push(b',');
+        }
+
+        batch.append(&mut self.element);
+        self.elements += 1;
+        self.state = State::Between;
+    }
+}
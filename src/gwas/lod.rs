@@ -0,0 +1,156 @@
+//! Aggregated versions of a chromosome's points, so that zoomed out
+//! views draw a point per bin rather than every record.
+//!
+//! Each level bins the chromosome at a fixed width and keeps the most
+//! significant record of each bin, along with how many records fell
+//! into it. Records at or above genome-wide significance are kept in
+//! every level, even when they aren't the maximum of their bin, so
+//! the top hits are never dropped.
+
+use std::collections::BTreeMap;
+
+/// The widths in basepairs of the bins of each level, finest first.
+pub const BIN_WIDTHS: [usize; 3] = [10_000, 100_000, 1_000_000];
+
+/// The most bins `LodPyramid::max_in` looks at, if a level is coarse
+/// enough.
+const MAX_SCANNED_BINS: usize = 4096;
+
+/// -log10(5e-8), the conventional genome-wide significance threshold.
+pub const TOP_HIT_NEG_LOG10_P: f64 = 7.301_029_995_663_981;
+
+/// The records of a chromosome that fall in one bin of a level.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    /// The index of the most significant record in the bin, among
+    /// the records of the chromosome.
+    pub record: usize,
+    pub max_neg_log10_p: f64,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodLevel {
+    pub bin_width: usize,
+
+    /// The non-empty bins, by their index along the chromosome.
+    pub bins: BTreeMap<usize, Bin>,
+
+    /// The indices of the records at or above `TOP_HIT_NEG_LOG10_P`
+    /// that aren't the maximum of their bin.
+    pub top_hits: Vec<usize>,
+}
+
+impl LodLevel {
+    pub fn new(bin_width: usize) -> Self {
+        Self {
+            bin_width,
+            bins: BTreeMap::new(),
+            top_hits: Vec::new(),
+        }
+    }
+
+    /// Adds the `record`th record of the chromosome.
+    pub fn insert(&mut self, record: usize, pos: usize, neg_log10_p: f64) {
+        let bin = self.bins.entry(pos / self.bin_width).or_insert(Bin {
+            record,
+            max_neg_log10_p: neg_log10_p,
+            count: 0,
+        });
+
+        bin.count += 1;
+
+        if bin.count == 1 {
+            return;
+        }
+
+        // whichever of the new record and the bin's current maximum
+        // doesn't represent the bin
+        let other = if neg_log10_p > bin.max_neg_log10_p {
+            let displaced = (bin.record, bin.max_neg_log10_p);
+            bin.record = record;
+            bin.max_neg_log10_p = neg_log10_p;
+            displaced
+        } else {
+            (record, neg_log10_p)
+        };
+
+        if other.1 >= TOP_HIT_NEG_LOG10_P {
+            self.top_hits.push(other.0);
+        }
+    }
+
+    /// The number of points the level is drawn with.
+    pub fn len(&self) -> usize {
+        self.bins.len() + self.top_hits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    /// The indices of the records to draw.
+    pub fn records(&self) -> impl Iterator<Item = usize> + '_ {
+        self.bins
+            .values()
+            .map(|bin| bin.record)
+            .chain(self.top_hits.iter().copied())
+    }
+}
+
+/// The levels of detail of one chromosome, finest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodPyramid {
+    pub levels: Vec<LodLevel>,
+}
+
+impl Default for LodPyramid {
+    fn default() -> Self {
+        Self {
+            levels: BIN_WIDTHS
+                .iter()
+                .map(|&width| LodLevel::new(width))
+                .collect(),
+        }
+    }
+}
+
+impl LodPyramid {
+    /// Adds the `record`th record of the chromosome.
+    pub fn insert(&mut self, record: usize, pos: usize, neg_log10_p: f64) {
+        for level in self.levels.iter_mut() {
+            level.insert(record, pos, neg_log10_p);
+        }
+    }
+
+    /// The index of the coarsest level whose bins are no wider than
+    /// `bp_per_pixel`, or `None` if the view is zoomed in far enough
+    /// that every record should be drawn.
+    pub fn level_for(&self, bp_per_pixel: f32) -> Option<usize> {
+        self.levels
+            .iter()
+            .rposition(|level| level.bin_width as f32 <= bp_per_pixel)
+    }
+
+    /// The largest -log10(p) in `start..end` of the chromosome. This
+    /// looks at whole bins, so records just outside the range may be
+    /// included; the finest level that has at most `MAX_SCANNED_BINS`
+    /// bins in the range is used.
+    pub fn max_in(&self, start: usize, end: usize) -> Option<f64> {
+        let len = end.saturating_sub(start);
+
+        let level = self
+            .levels
+            .iter()
+            .find(|level| len / level.bin_width <= MAX_SCANNED_BINS)
+            .or_else(|| self.levels.last())?;
+
+        level
+            .bins
+            .range(start / level.bin_width..=end / level.bin_width)
+            .map(|(_, bin)| bin.max_neg_log10_p)
+            .fold(None, |max: Option<f64>, p| {
+                Some(max.map_or(p, |max| max.max(p)))
+            })
+    }
+}
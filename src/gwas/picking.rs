@@ -0,0 +1,222 @@
+//! Finding the marker under the mouse on the GPU. The points are
+//! drawn a second time into an offscreen target whose pixels hold
+//! which dataset, chromosome and instance the topmost marker covering
+//! them belongs to, and the pixel under the mouse is read back.
+//!
+//! Reading back is asynchronous, so what's picked lags the frame
+//! being drawn by a frame or two, and is dropped if the view, the
+//! tracks or the mouse have changed in the meantime.
+
+use std::cell::{Cell, RefCell};
+use std::num::NonZeroU32;
+use std::rc::Rc;
+
+use crate::view::View;
+
+use super::TrackParams;
+
+/// The format of the picking target: the source of the marker,
+/// see `PickId::source`, and the index of its instance.
+pub const PICK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+
+/// The most chromosomes a coordinate system can have for its markers
+/// to be told apart. Picking isn't used with any that have more.
+pub const MAX_PICK_CHRS: usize = 256;
+
+/// The size of a pixel of the picking target.
+const TEXEL_SIZE: u64 = 8;
+
+/// What's drawn at a pixel of the picking target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PickId {
+    /// The ID of the dataset.
+    pub dataset: usize,
+    /// The index of the chromosome in the coordinate system.
+    pub chr_ix: usize,
+    /// The index of the instance in the buffer the chromosome was
+    /// drawn from, see `GwasDataChrs::record_for_instance`.
+    pub instance: usize,
+}
+
+impl PickId {
+    /// The number the markers of the `chr_ix`th chromosome of a
+    /// dataset are drawn into the picking target with. It's passed
+    /// to the shader as a float, which holds it exactly. Chromosomes
+    /// from `MAX_PICK_CHRS` on share the numbers of earlier ones.
+    pub fn source(dataset: usize, chr_ix: usize) -> f32 {
+        ((dataset + 1) * MAX_PICK_CHRS + chr_ix % MAX_PICK_CHRS) as f32
+    }
+
+    /// The marker a pixel of the picking target was drawn by, if any.
+    pub fn decode([source, instance]: [u32; 2]) -> Option<Self> {
+        let source = source as usize;
+
+        if source < MAX_PICK_CHRS {
+            return None;
+        }
+
+        Some(Self {
+            dataset: source / MAX_PICK_CHRS - 1,
+            chr_ix: source % MAX_PICK_CHRS,
+            instance: instance as usize,
+        })
+    }
+}
+
+/// How the picking target was drawn, and which of its pixels was
+/// read back. A pick only applies to frames drawn the same way with
+/// the mouse over the same pixel, as otherwise a different marker
+/// may be under it, its instance may be one of another level of
+/// detail, or of a dataset that has since changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PickFrame {
+    pub pixel: (u32, u32),
+    pub view: View,
+    pub bp_per_pixel: f32,
+    /// The tracks the datasets were drawn in.
+    pub tracks: Vec<TrackParams>,
+    /// The IDs of the datasets drawn, with their record counts.
+    pub datasets: Vec<(usize, usize)>,
+}
+
+/// The offscreen target the points are picked from, and the pixel
+/// last read back from it.
+pub struct PickingTarget {
+    size: (u32, u32),
+
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+
+    /// Holds the pixel under the mouse once it's been copied, with
+    /// room for a row of the alignment copies require.
+    readback: Rc<wgpu::Buffer>,
+    reading: Rc<Cell<bool>>,
+
+    picked: Rc<RefCell<Option<(PickId, PickFrame)>>>,
+}
+
+impl PickingTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Picking target"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PICK_FORMAT,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT | wgpu::TextureUsage::COPY_SRC,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Picking readback"),
+            size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            size: (width, height),
+
+            texture,
+            view,
+
+            readback: Rc::new(readback),
+            reading: Rc::new(Cell::new(false)),
+
+            picked: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Copies the pixel of `frame` to be read back, unless the last
+    /// one is still being read. Returns whether it did, in which case
+    /// `read` should be called once `encoder` has been submitted.
+    pub fn copy_pixel(&self, encoder: &mut wgpu::CommandEncoder, frame: &PickFrame) -> bool {
+        let (width, height) = self.size;
+        let (x, y) = frame.pixel;
+
+        if x >= width || y >= height {
+            self.clear_picked();
+            return false;
+        }
+
+        if self.reading.get() {
+            return false;
+        }
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        true
+    }
+
+    /// Reads back the pixel copied by `copy_pixel` from the target as
+    /// drawn for `frame`, after which it's returned by `picked`.
+    pub fn read(&self, frame: PickFrame) {
+        self.reading.set(true);
+
+        let readback = self.readback.clone();
+        let reading = self.reading.clone();
+        let picked = self.picked.clone();
+
+        wasm_bindgen_futures::spawn_local(async move {
+            let slice = readback.slice(..TEXEL_SIZE);
+
+            if slice.map_async(wgpu::MapMode::Read).await.is_ok() {
+                let texel = {
+                    let data = slice.get_mapped_range();
+                    let word = |ix: usize| {
+                        u32::from_le_bytes([data[ix], data[ix + 1], data[ix + 2], data[ix + 3]])
+                    };
+                    [word(0), word(4)]
+                };
+
+                readback.unmap();
+                *picked.borrow_mut() = PickId::decode(texel).map(|id| (id, frame));
+            }
+
+            reading.set(false);
+        });
+    }
+
+    /// The marker under the mouse as of the last pixel read back, if
+    /// it was drawn the same way as `frame`.
+    pub fn picked(&self, frame: &PickFrame) -> Option<PickId> {
+        match &*self.picked.borrow() {
+            Some((id, picked_frame)) if picked_frame == frame => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Forgets the last pixel read back, e.g. as nothing is drawn.
+    pub fn clear_picked(&self) {
+        *self.picked.borrow_mut() = None;
+    }
+}
@@ -0,0 +1,92 @@
+/// A single association test result, independent of the format it
+/// was loaded from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GwasRecord {
+    pub chr: String,
+    pub pos: usize,
+
+    /// The association p-value as -log10(p), which unlike p itself
+    /// stays representable for arbitrarily significant results.
+    pub neg_log10_p: f64,
+
+    pub rs: Option<String>,
+    pub allele1: Option<String>,
+    pub allele0: Option<String>,
+
+    pub af: Option<f64>,
+    pub beta: Option<f64>,
+    pub se: Option<f64>,
+
+    /// A categorical annotation, such as the variant's consequence.
+    pub annotation: Option<String>,
+    /// The LD r² with the lead variant of the region.
+    pub ld_r2: Option<f64>,
+}
+
+impl GwasRecord {
+    pub fn new(chr: &str, pos: usize, neg_log10_p: f64) -> Self {
+        Self {
+            chr: chr.to_string(),
+            pos,
+            neg_log10_p,
+
+            rs: None,
+            allele1: None,
+            allele0: None,
+
+            af: None,
+            beta: None,
+            se: None,
+
+            annotation: None,
+            ld_r2: None,
+        }
+    }
+
+    /// The p-value itself, which is 0 if it's too small for an `f64`.
+    pub fn p(&self) -> f64 {
+        10f64.powf(-self.neg_log10_p)
+    }
+}
+
+/// The smallest positive `f64`, which p-values of exactly 0 are
+/// clamped to; tools working in `f64` write 0 when a p-value
+/// underflows, so it stands for an extremely strong association.
+pub const MIN_P: f64 = 5e-324;
+
+/// Computes -log10(p) from a p-value, clamping 0 to `MIN_P`.
+/// Returns `None` if `p` isn't in [0, 1].
+pub fn neg_log10_p_value(p: f64) -> Option<f64> {
+    if !(0.0..=1.0).contains(&p) {
+        return None;
+    }
+
+    Some(-p.max(MIN_P).log10())
+}
+
+/// Computes -log10(p) from the textual representation of a p-value.
+///
+/// Values like `1e-400` underflow when parsed as an `f64`, so the
+/// mantissa and exponent are handled separately in that case, while
+/// a literal 0 is clamped as in `neg_log10_p_value`. Returns `None`
+/// if the text isn't a number in [0, 1].
+pub fn neg_log10_p(text: &str) -> Option<f64> {
+    let p: f64 = text.parse().ok()?;
+
+    if p == 0.0 {
+        if let Some((mantissa, exponent)) = text.split_once(&['e', 'E'][..]) {
+            let mantissa: f64 = mantissa.parse().ok()?;
+            let exponent: f64 = exponent.parse().ok()?;
+
+            if mantissa < 0.0 {
+                return None;
+            }
+
+            if mantissa > 0.0 {
+                return Some(-(mantissa.log10() + exponent));
+            }
+        }
+    }
+
+    neg_log10_p_value(p)
+}
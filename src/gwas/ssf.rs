@@ -0,0 +1,89 @@
+//! The GWAS Catalog's standard summary statistics format (GWAS-SSF),
+//! a tab-separated table with a YAML metadata sidecar.
+
+use std::collections::HashMap;
+
+use crate::error::LoadError;
+
+use super::record::GwasRecord;
+use super::table::{self, ColumnMapping, Delimiter, Header, PValueColumn, TableLayout};
+
+/// The file name suffix of the metadata sidecar, appended to the
+/// name of the data file.
+pub const SIDECAR_SUFFIX: &str = "-meta.yaml";
+
+/// Parses a GWAS-SSF table.
+pub fn parse_ssf(text: &str) -> Result<Vec<GwasRecord>, LoadError> {
+    table::parse_table(text, TableLayout::Ssf)
+}
+
+/// The column mapping for a GWAS-SSF table with the given header.
+/// The `-log10 p` column is preferred over the p-value column when
+/// both are present.
+pub fn mapping(header: &Header) -> ColumnMapping {
+    let p = if header.index_of("neg_log_10_p_value").is_some() {
+        PValueColumn::NegLog10P("neg_log_10_p_value".into())
+    } else {
+        PValueColumn::P("p_value".into())
+    };
+
+    let present = |names: &[&str]| {
+        header
+            .index_of_any(names)
+            .map(|ix| header.columns()[ix].clone())
+    };
+
+    ColumnMapping {
+        delimiter: Delimiter::Tab,
+
+        rs: present(&["rsid", "variant_id"]),
+        allele1: present(&["effect_allele"]),
+        allele0: present(&["other_allele"]),
+
+        af: present(&["effect_allele_frequency"]),
+        beta: present(&["beta"]),
+        se: present(&["standard_error"]),
+
+        ..ColumnMapping::new("chromosome", "base_pair_location", p)
+    }
+}
+
+/// The top-level fields of a GWAS-SSF metadata sidecar.
+///
+/// Only scalar `key: value` entries are kept; lists and nested
+/// mappings are skipped, as nothing here needs them.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SsfMetadata {
+    fields: HashMap<String, String>,
+}
+
+impl SsfMetadata {
+    pub fn parse_yaml(text: &str) -> Self {
+        let mut fields = HashMap::default();
+
+        for line in text.lines() {
+            // nested entries and list items are indented or start with `-`
+            if line.starts_with(|c: char| c.is_whitespace() || c == '-' || c == '#') {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once(':') {
+                let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+
+                if !value.is_empty() {
+                    fields.insert(key.trim().to_string(), value.to_string());
+                }
+            }
+        }
+
+        Self { fields }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(|s| s.as_str())
+    }
+
+    pub fn genome_assembly(&self) -> Option<&str> {
+        self.get("genome_assembly")
+    }
+}
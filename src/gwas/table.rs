@@ -0,0 +1,595 @@
+use crate::error::LoadError;
+
+use super::record::{self, GwasRecord};
+
+type Result<T> = std::result::Result<T, LoadError>;
+
+/// How the fields of a table row are separated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    /// Any run of spaces and tabs, as used by GEMMA and PLINK.
+    Whitespace,
+    Tab,
+    Comma,
+}
+
+impl Delimiter {
+    pub fn split<'a>(&self, line: &'a str) -> Vec<&'a str> {
+        match self {
+            Delimiter::Whitespace => line.split_whitespace().collect(),
+            Delimiter::Tab => line.split('\t').map(str::trim).collect(),
+            Delimiter::Comma => line.split(',').map(str::trim).collect(),
+        }
+    }
+}
+
+impl Default for Delimiter {
+    fn default() -> Self {
+        Delimiter::Whitespace
+    }
+}
+
+/// The column names of a delimited table, used to look up fields by
+/// name rather than by position.
+#[derive(Debug, Clone)]
+pub struct Header {
+    columns: Vec<String>,
+}
+
+impl Header {
+    pub fn parse(line: &str, delimiter: Delimiter) -> Self {
+        let columns = delimiter
+            .split(line)
+            .into_iter()
+            .map(String::from)
+            .collect();
+        Self { columns }
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|col| col == name)
+    }
+
+    /// Like `index_of`, but a missing column is an error; `field`
+    /// describes what the column is needed for.
+    pub fn require(&self, name: &str, field: &str) -> Result<usize> {
+        self.index_of(name).ok_or_else(|| LoadError::MissingColumn {
+            column: name.to_string(),
+            field: field.to_string(),
+        })
+    }
+
+    /// Finds the first of several alternative column names present in
+    /// the header.
+    pub fn index_of_any(&self, names: &[&str]) -> Option<usize> {
+        names.iter().find_map(|name| self.index_of(name))
+    }
+
+    pub fn require_any(&self, names: &[&str], field: &str) -> Result<usize> {
+        self.index_of_any(names)
+            .ok_or_else(|| LoadError::MissingColumn {
+                column: names.join("` or `"),
+                field: field.to_string(),
+            })
+    }
+}
+
+/// Which of GEMMA's test statistics to take the p-value from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GemmaPValue {
+    Wald,
+    Lrt,
+    Score,
+}
+
+impl GemmaPValue {
+    pub fn column(&self) -> &'static str {
+        match self {
+            GemmaPValue::Wald => "p_wald",
+            GemmaPValue::Lrt => "p_lrt",
+            GemmaPValue::Score => "p_score",
+        }
+    }
+}
+
+impl Default for GemmaPValue {
+    fn default() -> Self {
+        GemmaPValue::Wald
+    }
+}
+
+/// The column holding the association p-value, either as is or
+/// already transformed to -log10(p).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PValueColumn {
+    P(String),
+    NegLog10P(String),
+}
+
+impl PValueColumn {
+    pub fn name(&self) -> &str {
+        match self {
+            PValueColumn::P(name) => name,
+            PValueColumn::NegLog10P(name) => name,
+        }
+    }
+}
+
+/// Describes which columns of a delimited table hold which fields of
+/// a `GwasRecord`, so that tables from arbitrary tools can be loaded
+/// without preprocessing.
+///
+/// Every column named in the mapping must be present in the table's
+/// header, including the optional ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnMapping {
+    pub delimiter: Delimiter,
+
+    pub chr: String,
+    pub pos: String,
+    pub p: PValueColumn,
+
+    pub rs: Option<String>,
+    /// The effect allele.
+    pub allele1: Option<String>,
+    /// The other (non-effect) allele.
+    pub allele0: Option<String>,
+
+    pub af: Option<String>,
+    pub beta: Option<String>,
+    pub se: Option<String>,
+
+    /// A categorical annotation, such as the variant's consequence.
+    pub annotation: Option<String>,
+    /// The LD r² with the lead variant of the region.
+    pub ld_r2: Option<String>,
+
+    /// If set, only rows where the first column holds the second
+    /// value are kept, e.g. `("TEST", "ADD")`.
+    pub filter: Option<(String, String)>,
+}
+
+impl ColumnMapping {
+    /// A mapping with only the required columns set.
+    pub fn new(chr: &str, pos: &str, p: PValueColumn) -> Self {
+        Self {
+            delimiter: Delimiter::default(),
+
+            chr: chr.to_string(),
+            pos: pos.to_string(),
+            p,
+
+            rs: None,
+            allele1: None,
+            allele0: None,
+
+            af: None,
+            beta: None,
+            se: None,
+
+            annotation: None,
+            ld_r2: None,
+
+            filter: None,
+        }
+    }
+
+    pub fn gemma(p_value: GemmaPValue) -> Self {
+        let p = PValueColumn::P(p_value.column().to_string());
+
+        // the likelihood ratio test alone (`-lmm 2`) doesn't estimate
+        // the effect size
+        let effect = |column: &str| match p_value {
+            GemmaPValue::Lrt => None,
+            _ => Some(column.to_string()),
+        };
+
+        Self {
+            rs: Some("rs".into()),
+            allele1: Some("allele1".into()),
+            allele0: Some("allele0".into()),
+
+            af: Some("af".into()),
+            beta: effect("beta"),
+            se: effect("se"),
+
+            ..Self::new("chr", "ps", p)
+        }
+    }
+
+    pub fn regenie() -> Self {
+        let p = PValueColumn::NegLog10P("LOG10P".into());
+
+        Self {
+            rs: Some("ID".into()),
+            allele1: Some("ALLELE1".into()),
+            allele0: Some("ALLELE0".into()),
+
+            af: Some("A1FREQ".into()),
+            beta: Some("BETA".into()),
+            se: Some("SE".into()),
+
+            filter: Some(("TEST".into(), "ADD".into())),
+
+            ..Self::new("CHROM", "GENPOS", p)
+        }
+    }
+
+    pub fn saige() -> Self {
+        let p = PValueColumn::P("p.value".into());
+
+        Self {
+            rs: Some("MarkerID".into()),
+            allele1: Some("Allele2".into()),
+            allele0: Some("Allele1".into()),
+
+            af: Some("AF_Allele2".into()),
+            beta: Some("BETA".into()),
+            se: Some("SE".into()),
+
+            ..Self::new("CHR", "POS", p)
+        }
+    }
+
+    pub fn bolt_lmm() -> Self {
+        let p = PValueColumn::P("P_BOLT_LMM".into());
+
+        Self {
+            rs: Some("SNP".into()),
+            allele1: Some("ALLELE1".into()),
+            allele0: Some("ALLELE0".into()),
+
+            af: Some("A1FREQ".into()),
+            beta: Some("BETA".into()),
+            se: Some("SE".into()),
+
+            ..Self::new("CHR", "BP", p)
+        }
+    }
+
+    fn resolve(&self, header: &Header) -> Result<RecordColumns> {
+        let require = |name: &str, field: &str| header.require(name, field);
+
+        let optional = |name: &Option<String>, field: &str| {
+            name.as_ref().map(|name| require(name, field)).transpose()
+        };
+
+        let (p, p_is_neg_log10) = match &self.p {
+            PValueColumn::P(name) => (require(name, "p-value")?, false),
+            PValueColumn::NegLog10P(name) => (require(name, "-log10 p-value")?, true),
+        };
+
+        let filter = match &self.filter {
+            Some((name, value)) => Some((require(name, "row filter")?, value.clone())),
+            None => None,
+        };
+
+        Ok(RecordColumns {
+            chr: require(&self.chr, "chromosome")?,
+            pos: require(&self.pos, "position")?,
+            p,
+            p_is_neg_log10,
+
+            rs: optional(&self.rs, "SNP id")?,
+            allele1: optional(&self.allele1, "effect allele")?,
+            allele0: optional(&self.allele0, "other allele")?,
+
+            af: optional(&self.af, "allele frequency")?,
+            beta: optional(&self.beta, "effect size")?,
+            se: optional(&self.se, "standard error")?,
+
+            annotation: optional(&self.annotation, "annotation")?,
+            ld_r2: optional(&self.ld_r2, "LD r²")?,
+
+            filter,
+        })
+    }
+}
+
+/// How the columns of a table are found from its header line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TableLayout {
+    Mapped(ColumnMapping),
+    Plink,
+    Ssf,
+}
+
+impl TableLayout {
+    pub fn delimiter(&self) -> Delimiter {
+        match self {
+            TableLayout::Mapped(mapping) => mapping.delimiter,
+            TableLayout::Plink => Delimiter::Whitespace,
+            TableLayout::Ssf => Delimiter::Tab,
+        }
+    }
+
+    fn resolve(&self, header: &Header) -> Result<RecordColumns> {
+        match self {
+            TableLayout::Mapped(mapping) => mapping.resolve(header),
+            TableLayout::Plink => plink_columns(header),
+            TableLayout::Ssf => super::ssf::mapping(header).resolve(header),
+        }
+    }
+}
+
+/// Incrementally parses a delimited table from chunks of bytes, such
+/// as those of a streamed response body.
+///
+/// Lines may be split across chunks; the incomplete tail of each
+/// chunk is kept until the rest of it arrives.
+pub struct TableReader {
+    layout: TableLayout,
+    columns: Option<(Header, RecordColumns)>,
+
+    partial: Vec<u8>,
+    line_no: usize,
+
+    skip_bad_rows: bool,
+    skipped_rows: usize,
+}
+
+impl TableReader {
+    pub fn new(layout: TableLayout) -> Self {
+        Self {
+            layout,
+            columns: None,
+
+            partial: Vec::new(),
+            line_no: 0,
+
+            skip_bad_rows: false,
+            skipped_rows: 0,
+        }
+    }
+
+    /// If set, rows with missing or invalid fields are skipped and
+    /// counted rather than failing the whole table.
+    pub fn skip_bad_rows(mut self, skip: bool) -> Self {
+        self.skip_bad_rows = skip;
+        self
+    }
+
+    /// The number of rows skipped so far.
+    pub fn skipped_rows(&self) -> usize {
+        self.skipped_rows
+    }
+
+    /// Parses all complete lines available after appending `bytes`.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<GwasRecord>> {
+        self.partial.extend_from_slice(bytes);
+
+        let end = match self.partial.iter().rposition(|&b| b == b'\n') {
+            Some(ix) => ix + 1,
+            None => return Ok(Vec::new()),
+        };
+
+        let complete: Vec<u8> = self.partial.drain(..end).collect();
+        self.parse_lines(&complete)
+    }
+
+    /// Parses whatever is left once there are no more bytes.
+    pub fn finish(&mut self) -> Result<Vec<GwasRecord>> {
+        let rest = std::mem::take(&mut self.partial);
+        let records = self.parse_lines(&rest)?;
+
+        if self.columns.is_none() {
+            return Err(LoadError::Parse("empty table".into()));
+        }
+
+        Ok(records)
+    }
+
+    fn parse_lines(&mut self, bytes: &[u8]) -> Result<Vec<GwasRecord>> {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| LoadError::Parse(format!("line {}: invalid UTF-8", self.line_no + 1)))?;
+
+        let mut records = Vec::new();
+
+        for line in text.lines() {
+            match self.parse_line(line) {
+                Ok(Some(record)) => records.push(record),
+                Ok(None) => (),
+                Err(err) if self.skip_bad_rows && err.is_row_error() => self.skipped_rows += 1,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(records)
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<Option<GwasRecord>> {
+        self.line_no += 1;
+
+        if line.trim().is_empty() || line.starts_with("##") {
+            return Ok(None);
+        }
+
+        let delimiter = self.layout.delimiter();
+
+        match &self.columns {
+            Some((header, columns)) => parse_row(self.line_no, line, delimiter, header, columns),
+            None => {
+                let header = Header::parse(line, delimiter);
+                let columns = self.layout.resolve(&header)?;
+                self.columns = Some((header, columns));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Parses a whole table at once.
+pub fn parse_table(text: &str, layout: TableLayout) -> Result<Vec<GwasRecord>> {
+    let mut reader = TableReader::new(layout);
+
+    let mut records = reader.push(text.as_bytes())?;
+    records.extend(reader.finish()?);
+
+    Ok(records)
+}
+
+/// Parses a delimited table with a header line, using `mapping` to
+/// find the fields.
+pub fn parse_mapped(text: &str, mapping: &ColumnMapping) -> Result<Vec<GwasRecord>> {
+    parse_table(text, TableLayout::Mapped(mapping.clone()))
+}
+
+/// Parses GEMMA's whitespace-delimited `.assoc.txt` output.
+pub fn parse_gemma(text: &str, p_value: GemmaPValue) -> Result<Vec<GwasRecord>> {
+    parse_mapped(text, &ColumnMapping::gemma(p_value))
+}
+
+/// Parses the association output of PLINK 1.9 (`.assoc`, `.qassoc`,
+/// `.assoc.linear`, `.assoc.logistic`) and PLINK 2 (`.glm.*`).
+///
+/// If the table has a `TEST` column, only the additive (`ADD`) rows
+/// are kept.
+pub fn parse_plink(text: &str) -> Result<Vec<GwasRecord>> {
+    parse_table(text, TableLayout::Plink)
+}
+
+fn plink_columns(header: &Header) -> Result<RecordColumns> {
+    // neither column being present is reported as one missing column
+    let missing_p = || LoadError::MissingColumn {
+        column: "P` or `LOG10_P".to_string(),
+        field: "p-value".to_string(),
+    };
+
+    // PLINK 2 writes -log10(p) instead when run with `--glm log10`
+    let (p, p_is_neg_log10) = match header.index_of("P") {
+        Some(ix) => (ix, false),
+        None => {
+            let ix = header
+                .require("LOG10_P", "p-value")
+                .map_err(|_| missing_p())?;
+            (ix, true)
+        }
+    };
+
+    Ok(RecordColumns {
+        chr: header.require_any(&["CHR", "#CHROM"], "chromosome")?,
+        pos: header.require_any(&["BP", "POS"], "position")?,
+        p,
+        p_is_neg_log10,
+
+        rs: header.index_of_any(&["SNP", "ID"]),
+        allele1: header.index_of("A1"),
+        allele0: header.index_of_any(&["A2", "OMITTED", "AX"]),
+
+        af: header.index_of_any(&["A1_FREQ", "MAF"]),
+        beta: header.index_of("BETA"),
+        se: header.index_of_any(&["SE", "LOG(OR)_SE"]),
+
+        annotation: None,
+        ld_r2: None,
+
+        filter: header.index_of("TEST").map(|ix| (ix, "ADD".to_string())),
+    })
+}
+
+/// Column indices of the `GwasRecord` fields in a particular table.
+struct RecordColumns {
+    chr: usize,
+    pos: usize,
+    p: usize,
+    p_is_neg_log10: bool,
+
+    rs: Option<usize>,
+    allele1: Option<usize>,
+    allele0: Option<usize>,
+
+    af: Option<usize>,
+    beta: Option<usize>,
+    se: Option<usize>,
+
+    annotation: Option<usize>,
+    ld_r2: Option<usize>,
+
+    /// If set, rows whose value in this column differs from the
+    /// given one are skipped.
+    filter: Option<(usize, String)>,
+}
+
+/// Whether a field holds one of the usual missing-value markers.
+fn is_missing(field: &str) -> bool {
+    matches!(field, "" | "NA" | "nan" | "NaN" | ".")
+}
+
+fn parse_row(
+    line_no: usize,
+    line: &str,
+    delimiter: Delimiter,
+    header: &Header,
+    columns: &RecordColumns,
+) -> Result<Option<GwasRecord>> {
+    let fields = delimiter.split(line);
+
+    let name = |ix: usize| header.columns()[ix].clone();
+
+    let invalid = |ix: usize, val: &str| LoadError::InvalidValue {
+        row: line_no,
+        field: name(ix),
+        value: val.to_string(),
+    };
+
+    let field = |ix: usize| {
+        fields
+            .get(ix)
+            .copied()
+            .ok_or_else(|| LoadError::MissingField {
+                row: line_no,
+                field: name(ix),
+            })
+    };
+
+    let number = |ix: usize| -> Result<f64> {
+        let val = field(ix)?;
+        val.parse().map_err(|_| invalid(ix, val))
+    };
+
+    let string = |ix: Option<usize>| {
+        ix.and_then(|ix| fields.get(ix))
+            .filter(|s| !is_missing(s))
+            .map(|s| s.to_string())
+    };
+
+    if let Some((filter_ix, value)) = &columns.filter {
+        if field(*filter_ix)? != value {
+            return Ok(None);
+        }
+    }
+
+    // tests that failed to produce a p-value can't be plotted
+    if is_missing(field(columns.p)?) {
+        return Ok(None);
+    }
+
+    let chr = field(columns.chr)?;
+    let pos = number(columns.pos)? as usize;
+
+    let neg_log10_p = if columns.p_is_neg_log10 {
+        number(columns.p)?
+    } else {
+        let val = field(columns.p)?;
+        record::neg_log10_p(val).ok_or_else(|| invalid(columns.p, val))?
+    };
+
+    let mut record = GwasRecord::new(chr, pos, neg_log10_p);
+
+    record.rs = string(columns.rs);
+    record.allele1 = string(columns.allele1);
+    record.allele0 = string(columns.allele0);
+
+    record.af = columns.af.and_then(|ix| number(ix).ok());
+    record.beta = columns.beta.and_then(|ix| number(ix).ok());
+    record.se = columns.se.and_then(|ix| number(ix).ok());
+
+    record.annotation = string(columns.annotation);
+    record.ld_r2 = columns.ld_r2.and_then(|ix| number(ix).ok());
+
+    Ok(Some(record))
+}
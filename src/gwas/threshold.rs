@@ -0,0 +1,66 @@
+//! Significance thresholds, drawn as horizontal lines across the
+//! tracks.
+
+/// The family-wise error rate Bonferroni thresholds are computed for.
+pub const BONFERRONI_ALPHA: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    /// A fixed p-value.
+    P(f64),
+    /// `BONFERRONI_ALPHA` divided by the number of SNPs in the
+    /// dataset the line is drawn across.
+    Bonferroni,
+}
+
+impl Threshold {
+    /// The conventional genome-wide significance threshold.
+    pub const GENOME_WIDE: Self = Threshold::P(5e-8);
+    /// The conventional threshold for suggestive associations.
+    pub const SUGGESTIVE: Self = Threshold::P(1e-5);
+
+    pub fn defaults() -> Vec<Self> {
+        vec![Self::GENOME_WIDE, Self::SUGGESTIVE]
+    }
+
+    /// Parses a p-value in (0, 1], or `bonferroni`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+
+        if text.eq_ignore_ascii_case("bonferroni") {
+            return Some(Threshold::Bonferroni);
+        }
+
+        let p: f64 = text.parse().ok()?;
+
+        if p > 0.0 && p <= 1.0 {
+            Some(Threshold::P(p))
+        } else {
+            None
+        }
+    }
+
+    /// The p-value of the threshold for a dataset of `snp_count`
+    /// SNPs, or `None` for a Bonferroni threshold of an empty one.
+    pub fn p(&self, snp_count: usize) -> Option<f64> {
+        match self {
+            Threshold::P(p) => Some(*p),
+            Threshold::Bonferroni if snp_count > 0 => Some(BONFERRONI_ALPHA / snp_count as f64),
+            Threshold::Bonferroni => None,
+        }
+    }
+
+    /// The threshold as -log10(p), for a dataset of `snp_count` SNPs.
+    pub fn neg_log10_p(&self, snp_count: usize) -> Option<f64> {
+        self.p(snp_count).map(|p| -p.log10())
+    }
+
+    /// A label for the line, e.g. `p = 5e-8`.
+    pub fn label(&self, snp_count: usize) -> String {
+        match (self, self.p(snp_count)) {
+            (Threshold::P(p), _) => format!("p = {:e}", p),
+            (Threshold::Bonferroni, Some(p)) => format!("Bonferroni, p = {:.1e}", p),
+            (Threshold::Bonferroni, None) => "Bonferroni".to_string(),
+        }
+    }
+}
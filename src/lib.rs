@@ -1,15 +1,28 @@
 // mod animation;
-mod coordinates;
+mod api;
+mod config;
+pub mod coordinates;
+mod error;
+pub mod fetch;
+mod genes;
 mod geometry;
 mod gui;
-mod gwas;
+pub mod gwas;
+mod search;
+mod selection;
 mod state;
 mod utils;
 mod view;
 
+use api::{Browser, BrowserShared, Callbacks, Command};
+use config::{AppConfig, DatasetConfig};
 use coordinates::CoordinateSystem;
-use gwas::{GwasDataChrs, GwasUniforms};
-use state::SharedState;
+use error::LoadError;
+use genes::GeneIndex;
+use gwas::{
+    GwasDataChrs, GwasRecord, GwasUniforms, Legend, LoadOptions, LoadSummary, PickFrame, PickId,
+    PickingTarget, RecordBatch, RecordHit, TrackParams, YAxisMode,
+};
 use view::View;
 use wasm_bindgen::prelude::*;
 
@@ -21,6 +34,11 @@ use wasm_bindgen_futures::JsFuture;
 use web_sys::{Request, RequestInit, RequestMode, Response};
 
 use geometry::{Point, Vertex};
+use gui::SelectionAction;
+use selection::SelectedRecord;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use instant::Instant;
 
@@ -37,11 +55,58 @@ macro_rules! include_shader {
     };
 }
 
+/// The number of basepairs between consecutive chromosomes when the
+/// whole genome is laid out on one axis.
+const CHR_PADDING: usize = 50_000_000;
+
+/// How far in pixels the mouse has to move while pressed for it to
+/// drag out a box rather than click.
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// How long in seconds the view takes to fly to a search hit.
+const FLIGHT_SECONDS: f32 = 0.6;
+
 /// A custom event type for the winit app.
 enum AppEvent {
     RequestRedraw,
 }
 
+/// Progress of a dataset that's being loaded in the background,
+/// tagged with the ID of the dataset.
+enum LoadEvent {
+    Records(usize, RecordBatch),
+    Finished(usize, Result<LoadSummary, LoadError>),
+}
+
+/// The colours of the tracks that aren't given one, assigned in the
+/// order the datasets are added.
+const TRACK_COLORS: [[f32; 4]; 6] = [
+    [0.2, 0.2, 0.9, 1.0],
+    [0.9, 0.45, 0.1, 1.0],
+    [0.2, 0.7, 0.3, 1.0],
+    [0.8, 0.2, 0.5, 1.0],
+    [0.55, 0.35, 0.85, 1.0],
+    [0.85, 0.75, 0.2, 1.0],
+];
+
+/// A dataset that's been loaded, or is being loaded, and the track
+/// it's drawn in.
+struct Dataset {
+    id: usize,
+    label: String,
+    color: [f32; 4],
+    data: GwasDataChrs,
+
+    /// Each dataset needs its own uniforms, as the tracks differ in
+    /// position, y-range and colour.
+    uniforms: GwasUniforms,
+
+    /// Records on chromosomes missing from the coordinate system, by
+    /// chromosome, reported once loading is done. They're kept in case
+    /// an alias is added for the chromosome later.
+    unknown_chrs: HashMap<String, Vec<RecordBatch>>,
+}
+
 // struct ExampleRepaintSignal(std::sync::Mutex<winit::event_loop::EventLoopProxy<Event>>);
 struct ExampleRepaintSignal();
 
@@ -52,12 +117,41 @@ impl epi::RepaintSignal for ExampleRepaintSignal {
 }
 
 use winit::{
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event::{Event, ModifiersState, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy},
     window::Window,
 };
 
-async fn run(event_loop: EventLoop<()>, window: Window) {
+/// Starts loading a dataset in the background, sending its records
+/// to `load_tx` as they arrive.
+fn spawn_loader(
+    id: usize,
+    dataset: &DatasetConfig,
+    options: LoadOptions,
+    load_tx: crossbeam::channel::Sender<LoadEvent>,
+    proxy: EventLoopProxy<AppEvent>,
+) {
+    let url = dataset.url.clone();
+    let format = dataset.format.clone();
+
+    wasm_bindgen_futures::spawn_local(async move {
+        let result = gwas::stream_records(&url, format, options, |records| {
+            load_tx.send(LoadEvent::Records(id, records)).ok();
+            proxy.send_event(AppEvent::RequestRedraw).ok();
+        })
+        .await;
+
+        load_tx.send(LoadEvent::Finished(id, result)).ok();
+        proxy.send_event(AppEvent::RequestRedraw).ok();
+    });
+}
+
+async fn run(
+    event_loop: EventLoop<AppEvent>,
+    window: Window,
+    config: AppConfig,
+    shared: BrowserShared,
+) {
     /*
     request
         .headers()
@@ -91,16 +185,45 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let swapchain_format = adapter.get_swap_chain_preferred_format(&surface).unwrap();
 
-    let mouse_chrs = CoordinateSystem::fetch_and_parse("http://localhost:8080/mouse_chrs.json")
-        .await
-        .unwrap();
+    // errors are shown in the GUI rather than aborting, so the
+    // browser still comes up if e.g. the coordinate system is missing;
+    // each is labelled with the source it came from
+    let mut load_errors: Vec<(String, LoadError)> = Vec::new();
 
-    let gwas_chr_data = GwasDataChrs::fetch_and_parse(&device, "http://localhost:8080/gwas.json")
-        .await
-        .unwrap();
+    match CoordinateSystem::fetch_and_parse(&config.coordinates_url).await {
+        Ok(coords) => {
+            shared.coords.replace(coords);
+        }
+        Err(err) => load_errors.push(("Coordinate system".to_string(), err)),
+    }
+
+    let mut genes = GeneIndex::default();
+
+    if let Some(url) = &config.genes_url {
+        match GeneIndex::fetch_and_parse(url).await {
+            Ok(index) => genes = index,
+            Err(err) => load_errors.push(("Genes".to_string(), err)),
+        }
+    }
+
+    // shared with the `Browser` handle
+    let mouse_chrs = shared.coords.clone();
+    let state = shared.state.clone();
+    let callbacks = shared.callbacks.clone();
+
+    let (load_tx, load_rx) = crossbeam::channel::unbounded::<LoadEvent>();
+    let proxy = event_loop.create_proxy();
+
+    let options = LoadOptions {
+        skip_bad_rows: config.skip_bad_rows,
+    };
+
+    // filled by the commands the `Browser` handle sends, including
+    // the datasets of the configuration
+    let mut datasets: Vec<Dataset> = Vec::new();
 
-    let chr_offsets = mouse_chrs.chr_offsets(50_000_000);
-    let chr_ranges = mouse_chrs.chr_ranges(50_000_000);
+    let chr_offsets = mouse_chrs.borrow().chr_offsets(CHR_PADDING);
+    let chr_ranges = mouse_chrs.borrow().chr_ranges(CHR_PADDING);
 
     // not actually the total len, as it doesn't take the length of
     // the last chr into account, but good enough for now
@@ -108,12 +231,6 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     let mut gwas_pipeline = gwas::GwasPipeline::new(&device, swapchain_format).unwrap();
 
-    let mut uniforms = GwasUniforms::new(
-        &device,
-        &gwas_pipeline.bind_group_layout,
-        mouse_chrs.chr_names(),
-    );
-
     let mut init_view = View {
         center: (total_len as f32) / 2.0,
         ..View::default()
@@ -121,13 +238,44 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
 
     init_view.scale = 0.55 * init_view.base_bp_width * total_len as f32;
 
-    let state = SharedState {
-        view: Default::default(),
-        mouse_pos: Default::default(),
-    };
-
     state.view.store(init_view);
 
+    let mut layout = config.layout;
+    let mut coloring = config.coloring;
+    let mut thresholds = config.thresholds;
+    let mut highlight_significant = config.highlight_significant;
+    let mut y_axis = config.y_axis;
+    let mut compress_above = config.compress_above;
+    let mut gpu_picking = can_pick_on_gpu(config.gpu_picking, chr_offsets.len());
+
+    // created once GPU picking is enabled, and resized with the window
+    let mut picking: Option<PickingTarget> = None;
+
+    let mut modifiers = ModifiersState::default();
+
+    // the tracks as last drawn, for mapping the mouse to -log10(p)
+    let mut last_tracks: Vec<TrackParams> = Vec::new();
+
+    // the last view the `on_view_change` callback was called with
+    let mut last_view = None;
+
+    // the view being flown from and to after a search, and when the
+    // flight started
+    let mut flight: Option<(View, View, Instant)> = None;
+
+    // the genome-wide range of the last search hit
+    let mut search_hit: Option<(usize, usize)> = None;
+    // the record a search found, as its dataset, genome-wide position
+    // and -log10(p) value
+    let mut search_record: Option<(usize, usize, f32)> = None;
+
+    // where the left mouse button was pressed, while it's held
+    let mut drag_start: Option<Point> = None;
+
+    // the selection as the instance buffers last reflected it
+    let mut selection_generation = 0;
+    let mut selection_dirty = false;
+
     let mut sc_desc = wgpu::SwapChainDescriptor {
         usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
         format: swapchain_format,
@@ -161,7 +309,7 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
         // `event_loop.run` never returns, therefore we must do this to ensure
         // the resources are properly cleaned up.
         // let _ = (&instance, &adapter, &vs, &fs, &pipeline_layout);
-        let _ = (&instance, &adapter, &gwas_pipeline, &gwas_chr_data);
+        let _ = (&instance, &adapter, &gwas_pipeline, &datasets);
 
         gui.platform.handle_event(&event);
 
@@ -179,6 +327,165 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             Event::MainEventsCleared => {
                 // Event::RedrawRequested(_) => {
 
+                for command in shared.commands.try_iter() {
+                    match command {
+                        Command::LoadDataset(id, dataset) => {
+                            spawn_loader(id, &dataset, options, load_tx.clone(), proxy.clone());
+
+                            let uniforms = GwasUniforms::new(
+                                &device,
+                                &gwas_pipeline.bind_group_layout,
+                                mouse_chrs.borrow().chr_names(),
+                            );
+
+                            let color = dataset
+                                .color
+                                .unwrap_or(TRACK_COLORS[id % TRACK_COLORS.len()]);
+
+                            let mut data = GwasDataChrs::default();
+                            data.set_color_by(&device, &queue, coloring.by);
+
+                            datasets.push(Dataset {
+                                id,
+                                label: dataset.label,
+                                color,
+                                data,
+                                uniforms,
+                                unknown_chrs: HashMap::default(),
+                            });
+                        }
+                        Command::RemoveDataset(id) => {
+                            datasets.retain(|dataset| dataset.id != id);
+                            state.selection.lock().unwrap().remove_dataset(id);
+                        }
+                        Command::SetLayout(new_layout) => layout = new_layout,
+                        Command::SetColoring(new_coloring) => {
+                            coloring = new_coloring;
+
+                            for dataset in datasets.iter_mut() {
+                                dataset.data.set_color_by(&device, &queue, coloring.by);
+                            }
+
+                            selection_dirty = true;
+                        }
+                        Command::SetThresholds(new_thresholds) => thresholds = new_thresholds,
+                        Command::SetHighlight(highlight) => highlight_significant = highlight,
+                        Command::SetYAxis(mode) => y_axis = mode,
+                        Command::SetCompressAbove(cutoff) => compress_above = cutoff,
+                        Command::SetGpuPicking(enabled) => {
+                            gpu_picking = can_pick_on_gpu(enabled, chr_offsets.len());
+                        }
+                        Command::AddChrAlias(alias, chr) => {
+                            if !mouse_chrs.borrow_mut().add_chr_alias(&alias, &chr) {
+                                let msg = format!(
+                                    "Chromosome {} not found, ignoring alias {}",
+                                    chr, alias
+                                );
+                                web_sys::console::warn_1(&msg.into());
+                                continue;
+                            }
+
+                            // the records of datasets already loaded that
+                            // the alias applies to are no longer unknown
+                            for dataset in datasets.iter_mut() {
+                                let resolved = resolve_unknown_chrs(
+                                    &mouse_chrs.borrow(),
+                                    dataset,
+                                    &device,
+                                    &queue,
+                                );
+
+                                load_errors.retain(|(label, err)| match err {
+                                    LoadError::UnknownChromosome { chr, .. } => {
+                                        *label != dataset.label || !resolved.contains(chr)
+                                    }
+                                    _ => true,
+                                });
+                            }
+                        }
+                        Command::SetYRange(range) => {
+                            let mut view = state.view.load();
+                            view.y_range = range;
+                            state.view.store(view);
+                        }
+                        Command::SetView(region) => {
+                            match region.view(&mouse_chrs.borrow(), CHR_PADDING) {
+                                Some(view) => state.view.store(view),
+                                None => {
+                                    let msg = format!(
+                                        "Chromosome {} not found, or the range is past its end",
+                                        region.chr
+                                    );
+                                    web_sys::console::warn_1(&msg.into());
+                                }
+                            }
+                        }
+                    }
+                }
+
+                for load_event in load_rx.try_iter() {
+                    match load_event {
+                        LoadEvent::Records(id, batch) => {
+                            let dataset = match datasets.iter_mut().find(|d| d.id == id) {
+                                Some(dataset) => dataset,
+                                None => continue,
+                            };
+
+                            let known = resolve_chrs(
+                                &mouse_chrs.borrow(),
+                                &mut dataset.unknown_chrs,
+                                batch,
+                            );
+
+                            if let Some(batch) = known {
+                                dataset.data.append_batch(&device, &queue, batch);
+                            }
+                        }
+                        LoadEvent::Finished(id, result) => {
+                            let dataset = match datasets.iter_mut().find(|d| d.id == id) {
+                                Some(dataset) => dataset,
+                                None => continue,
+                            };
+
+                            let mut unknown: Vec<(String, usize)> = dataset
+                                .unknown_chrs
+                                .iter()
+                                .map(|(chr, batches)| {
+                                    (chr.clone(), batches.iter().map(RecordBatch::len).sum())
+                                })
+                                .collect();
+                            unknown.sort();
+
+                            for (chr, rows) in unknown {
+                                let err = LoadError::UnknownChromosome { chr, rows };
+                                load_errors.push((dataset.label.clone(), err));
+                            }
+
+                            match result {
+                                Ok(summary) => {
+                                    dataset.data.assembly = summary.assembly;
+                                    dataset.data.skipped_rows = summary.skipped_rows;
+                                    check_assembly(&mouse_chrs.borrow(), dataset);
+                                }
+                                Err(err) => {
+                                    let msg = format!("Error loading {}: {}", dataset.label, err);
+                                    web_sys::console::error_1(&msg.into());
+                                    load_errors.push((dataset.label.clone(), err));
+
+                                    // the batches that arrived before the
+                                    // error are only part of the dataset
+                                    let mut data = GwasDataChrs::default();
+                                    data.set_color_by(&device, &queue, coloring.by);
+                                    dataset.data = data;
+                                    dataset.unknown_chrs.clear();
+
+                                    state.selection.lock().unwrap().remove_dataset(id);
+                                }
+                            }
+                        }
+                    }
+                }
+
                 gui.platform.update_time(start_time.elapsed().as_secs_f64());
 
                 let frame = swap_chain
@@ -196,12 +503,257 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     log_timer = Instant::now();
                 }
 
-                gui.draw_chr_labels(
-                    &chr_ranges,
-                    state.view.load(),
-                    (sc_desc.height as f32) * 0.95,
-                    log,
-                );
+                if let Some(query) = gui.draw_search() {
+                    if query.trim().is_empty() {
+                        search_hit = None;
+                        search_record = None;
+                        gui.search_error = None;
+                    } else {
+                        let result = search::search(
+                            &query,
+                            &mouse_chrs.borrow(),
+                            datasets.iter().map(|d| (d.id, &d.data)),
+                            &genes,
+                        );
+
+                        match result {
+                            Ok(hit) => {
+                                let chr_range = chr_ranges
+                                    .iter()
+                                    .find(|(name, _)| *name == hit.region.chr)
+                                    .map(|(_, range)| *range);
+
+                                if let Some((chr_start, chr_end)) = chr_range {
+                                    let (start, end) = hit.range(chr_end - chr_start);
+                                    let region = config::Region {
+                                        chr: hit.region.chr.clone(),
+                                        range: Some((start, end)),
+                                    };
+
+                                    let target = region.view(&mouse_chrs.borrow(), CHR_PADDING);
+
+                                    if let Some(target) = target {
+                                        let from = state.view.load();
+                                        let to = View {
+                                            y_range: from.y_range,
+                                            ..target
+                                        };
+                                        flight = Some((from, to, Instant::now()));
+                                    }
+
+                                    search_hit = Some((chr_start + start, chr_start + end));
+                                    search_record = hit.record.map(|selected| {
+                                        let record = &selected.record;
+                                        let value = record.neg_log10_p as f32;
+                                        (selected.dataset, chr_start + record.pos, value)
+                                    });
+                                }
+
+                                gui.search_error = None;
+                            }
+                            Err(msg) => gui.search_error = Some(msg),
+                        }
+                    }
+                }
+
+                if let Some((from, to, started)) = flight {
+                    let t = started.elapsed().as_secs_f32() / FLIGHT_SECONDS;
+                    state.view.store(from.lerp(&to, t));
+
+                    if t >= 1.0 {
+                        flight = None;
+                    }
+                }
+
+                if let Some(range) = search_hit {
+                    gui.draw_search_hit(state.view.load(), range);
+                }
+
+                for axis_y in layout.chr_axes(datasets.len()) {
+                    gui.draw_chr_labels(
+                        &chr_ranges,
+                        state.view.load(),
+                        (1.0 - axis_y) * 0.5 * sc_desc.height as f32,
+                        log,
+                    );
+                }
+
+                // each dataset gets its own track, scaled to its own
+                // range of values, unless the view has been zoomed
+                // vertically
+                let view_range = state.view.load().bp_range();
+                let y_range = state.view.load().y_range;
+
+                let mut tracks: Vec<TrackParams> = datasets
+                    .iter()
+                    .enumerate()
+                    .map(|(ix, dataset)| {
+                        let visible_max = match y_axis {
+                            YAxisMode::Auto => dataset.data.visible_max(&chr_ranges, view_range),
+                            _ => None,
+                        };
+
+                        let data_max = dataset.data.max_neg_log10_p;
+
+                        let (min_y, max_y, truncated) = match y_range {
+                            Some((min, max)) => (min, max, data_max > max),
+                            None => {
+                                let (max, truncated) = y_axis.max_y(data_max, visible_max);
+                                (0.0, max, truncated)
+                            }
+                        };
+
+                        let mut track = layout.track(ix, datasets.len(), max_y, dataset.color);
+                        track.min_y = min_y;
+                        track.truncated = truncated;
+                        track.compress_above = compress_above;
+                        track
+                    })
+                    .collect();
+
+                // the thresholds of each track as -log10(p), strictest
+                // first, along with their labels
+                let threshold_lines: Vec<Vec<(f32, String)>> = datasets
+                    .iter()
+                    .map(|dataset| {
+                        let snp_count = dataset.data.record_count();
+
+                        let mut lines: Vec<(f32, String)> = thresholds
+                            .iter()
+                            .filter_map(|threshold| {
+                                let y = threshold.neg_log10_p(snp_count)? as f32;
+                                Some((y, threshold.label(snp_count)))
+                            })
+                            .collect();
+
+                        lines.sort_by(|(a, _), (b, _)| b.partial_cmp(a).unwrap());
+                        lines
+                    })
+                    .collect();
+
+                if highlight_significant {
+                    for (track, lines) in tracks.iter_mut().zip(threshold_lines.iter()) {
+                        track.highlight_above = lines.first().map(|(y, _)| *y);
+                    }
+                }
+
+                let track_labels: Vec<(&str, TrackParams)> = datasets
+                    .iter()
+                    .zip(tracks.iter())
+                    .map(|(dataset, track)| (dataset.label.as_str(), *track))
+                    .collect();
+
+                last_tracks = tracks.clone();
+
+                gui.draw_track_labels(&track_labels);
+                gui.draw_y_axes(&tracks);
+
+                if let Some((dataset, bp, value)) = search_record {
+                    let track = datasets
+                        .iter()
+                        .position(|d| d.id == dataset)
+                        .and_then(|ix| tracks.get(ix));
+
+                    if let Some(track) = track {
+                        gui.draw_search_record(state.view.load(), track, bp, value);
+                    }
+                }
+
+                let track_thresholds: Vec<(TrackParams, &[(f32, String)])> = tracks
+                    .iter()
+                    .zip(threshold_lines.iter())
+                    .map(|(track, lines)| (*track, lines.as_slice()))
+                    .collect();
+
+                gui.draw_thresholds(&track_thresholds);
+
+                let legends: Vec<(&str, Legend)> = datasets
+                    .iter()
+                    .filter_map(|d| Some((d.label.as_str(), d.data.legend(&coloring)?)))
+                    .collect();
+
+                gui.draw_color_legend(&legends);
+
+                // the record under the mouse, over all tracks
+                let view = state.view.load();
+                let mouse_pos = state.mouse_pos.load();
+                let bp_per_pixel = view.bp_per_pixel(sc_desc.width as f32);
+
+                let hovered = match &picking {
+                    Some(target) => {
+                        let frame = pick_frame(&datasets, &tracks, view, bp_per_pixel, mouse_pos);
+                        let picked = target.picked(&frame);
+                        picked_record(&datasets, &chr_offsets, bp_per_pixel, picked)
+                    }
+                    None => nearest_record(
+                        &datasets,
+                        &tracks,
+                        &chr_ranges,
+                        &view,
+                        bp_per_pixel,
+                        to_clip(mouse_pos, &sc_desc),
+                    ),
+                };
+
+                if let Some((dataset, hit)) = hovered {
+                    gui.draw_record_tooltip(mouse_pos, &dataset.label, &hit.record);
+                }
+
+                if let Some(start) = drag_start {
+                    if start.dist(mouse_pos) >= DRAG_THRESHOLD {
+                        gui.draw_selection_box(start, mouse_pos);
+                    }
+                }
+
+                let selection_action = {
+                    let labels: HashMap<usize, &str> =
+                        datasets.iter().map(|d| (d.id, d.label.as_str())).collect();
+
+                    let selection = state.selection.lock().unwrap();
+                    gui.draw_selection(selection.records(), &labels)
+                };
+
+                match selection_action {
+                    Some(SelectionAction::Remove(ix)) => state.selection.lock().unwrap().remove(ix),
+                    Some(SelectionAction::Clear) => state.selection.lock().unwrap().clear(),
+                    None => (),
+                }
+
+                let changed_selection = {
+                    let selection = state.selection.lock().unwrap();
+                    let mut changed = None;
+
+                    if selection.generation() != selection_generation || selection_dirty {
+                        for dataset in datasets.iter_mut() {
+                            dataset
+                                .data
+                                .set_selection(&device, selection.of_dataset(dataset.id));
+                        }
+
+                        if selection.generation() != selection_generation {
+                            changed = Some(api::selection_js(&selection));
+                        }
+
+                        selection_generation = selection.generation();
+                        selection_dirty = false;
+                    }
+
+                    changed
+                };
+
+                // only once the lock is released, as the callback may
+                // well call back into the API for the selection
+                if let Some(selection) = changed_selection {
+                    Callbacks::selection_change(&callbacks, &selection);
+                }
+
+                let skipped_rows: Vec<(String, usize)> = datasets
+                    .iter()
+                    .filter(|d| d.data.skipped_rows > 0)
+                    .map(|d| (d.label.clone(), d.data.skipped_rows))
+                    .collect();
+
+                gui.draw_load_errors(&load_errors, &skipped_rows);
 
                 let rect = gui.platform.context().input().screen_rect();
 
@@ -219,30 +771,99 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 }
                 .build();
 
-                let view = state.view.load();
+                if last_view != Some(view) {
+                    last_view = Some(view);
+                    let view_js = api::view_js(&mouse_chrs.borrow(), view);
+                    Callbacks::view_change(&callbacks, &view_js);
+                }
 
-                uniforms.write_uniforms(
-                    &device,
-                    &queue,
-                    &chr_offsets,
-                    view,
-                    -0.8,
-                    gwas_chr_data.min_p,
-                );
+                for (dataset, track) in datasets.iter_mut().zip(tracks.iter()) {
+                    let color_range = dataset.data.color_range();
+                    dataset.uniforms.write_uniforms(
+                        &device,
+                        &queue,
+                        &chr_offsets,
+                        view,
+                        dataset.id,
+                        track,
+                        &coloring,
+                        color_range,
+                    );
+                    dataset.data.update_lod_buffers(&device);
+                }
 
                 let mut encoder =
                     device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
 
                 let mut clear = true;
 
-                for (chr, bind_group) in uniforms.bind_groups.iter() {
-                    if let Some(buf) = gwas_chr_data.vertex_buffers.get(chr) {
-                        let count = gwas_chr_data.vertex_counts.get(chr).unwrap();
+                for dataset in datasets.iter() {
+                    let data = &dataset.data;
 
-                        let buf = buf.slice(..);
+                    for (chr, bind_group) in dataset.uniforms.bind_groups.iter() {
+                        if let Some((buf, count)) = data.instances(chr, bp_per_pixel) {
+                            gwas_pipeline.draw(&mut encoder, &frame, buf, bind_group, count, clear);
+                            clear = false;
+                        }
+                    }
+                }
+
+                // the selected points go on top of all the tracks
+                for dataset in datasets.iter() {
+                    let data = &dataset.data;
 
-                        gwas_pipeline.draw(&mut encoder, &frame, buf, bind_group, *count, clear);
-                        clear = false;
+                    for (chr, bind_group) in dataset.uniforms.bind_groups.iter() {
+                        if let Some((buf, count)) = data.selected_instances(chr) {
+                            gwas_pipeline.draw(&mut encoder, &frame, buf, bind_group, count, clear);
+                            clear = false;
+                        }
+                    }
+                }
+
+                // the points are drawn again for picking, and the pixel
+                // under the mouse read back once the frame's submitted
+                let size = (sc_desc.width, sc_desc.height);
+
+                if !gpu_picking {
+                    picking = None;
+                } else if picking
+                    .as_ref()
+                    .map_or(true, |target| target.size() != size)
+                {
+                    picking = Some(PickingTarget::new(&device, size.0, size.1));
+                }
+
+                let mut pick_copied = None;
+
+                if let Some(target) = &picking {
+                    let mut clear = true;
+
+                    for dataset in datasets.iter() {
+                        let data = &dataset.data;
+
+                        for (chr, bind_group) in dataset.uniforms.bind_groups.iter() {
+                            if let Some((buf, count)) = data.instances(chr, bp_per_pixel) {
+                                gwas_pipeline.draw_picking(
+                                    &mut encoder,
+                                    target,
+                                    buf,
+                                    bind_group,
+                                    count,
+                                    clear,
+                                );
+                                clear = false;
+                            }
+                        }
+                    }
+
+                    if clear {
+                        target.clear_picked();
+                    } else {
+                        let frame = pick_frame(&datasets, &tracks, view, bp_per_pixel, mouse_pos);
+
+                        if target.copy_pixel(&mut encoder, &frame) {
+                            pick_copied = Some(frame);
+                        }
                     }
                 }
 
@@ -283,6 +904,12 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 // queue.submit(iter::once(encoder.finish()));
                 queue.submit(Some(encoder.finish()));
                 // *control_flow = ControlFlow::Poll;
+
+                if let (Some(target), Some(frame)) = (&picking, pick_copied) {
+                    target.read(frame);
+                }
+
+                device.poll(wgpu::Maintain::Poll);
             }
             Event::WindowEvent {
                 event: WindowEvent::KeyboardInput { input, .. },
@@ -290,11 +917,24 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
             } => {
                 // web_sys::console::log_1(&format!("random: {}", val).into());
 
+                // keys typed into the search box are its own
+                if gui.platform.context().wants_keyboard_input() {
+                    return;
+                }
+
                 use winit::event::VirtualKeyCode as Key;
 
+                flight = None;
+
                 let mut view = state.view.load();
                 let w = sc_desc.width as f32;
 
+                // the range to pan from if the view hasn't been zoomed
+                // vertically yet
+                let y_range = view
+                    .y_range
+                    .or_else(|| last_tracks.first().map(|t| (t.min_y, t.max_y)));
+
                 match input.virtual_keycode {
                     Some(Key::Left) => {
                         view.center -= (5.0 * view.scale) / w;
@@ -302,6 +942,16 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                     Some(Key::Right) => {
                         view.center += (5.0 * view.scale) / w;
                     }
+                    Some(Key::Up) => {
+                        view.y_range = y_range.map(|range| View::pan_y(range, 0.05));
+                    }
+                    Some(Key::Down) => {
+                        view.y_range = y_range.map(|range| View::pan_y(range, -0.05));
+                    }
+                    Some(Key::F) => {
+                        view.y_range = None;
+                        y_axis = YAxisMode::Auto;
+                    }
                     _ => (),
                 }
 
@@ -314,23 +964,159 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
                 state
                     .mouse_pos
                     .store(Point::new(position.x as f32, position.y as f32));
+
+                let bp = state
+                    .view
+                    .load()
+                    .x_to_bp(position.x as f32, sc_desc.width as f32);
+
+                if let Some(pos) = api::position_js(&mouse_chrs.borrow(), bp) {
+                    Callbacks::hover(&callbacks, &pos);
+                }
             }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: winit::event::ElementState::Pressed,
+                        button: winit::event::MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => {
+                // clicks on the windows of the GUI are theirs
+                if gui.platform.context().is_pointer_over_area() {
+                    return;
+                }
+
+                drag_start = Some(state.mouse_pos.load());
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state: winit::event::ElementState::Released,
+                        button: winit::event::MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => {
+                let start = match drag_start.take() {
+                    Some(start) => start,
+                    None => return,
+                };
+
+                let view = state.view.load();
+                let mouse_pos = state.mouse_pos.load();
+                let bp_per_pixel = view.bp_per_pixel(sc_desc.width as f32);
+
+                // only a release close to the press is a click, the
+                // rest end a drag
+                let clicked = start.dist(mouse_pos) < DRAG_THRESHOLD;
+
+                if clicked {
+                    let bp = view.x_to_bp(mouse_pos.x, sc_desc.width as f32);
+
+                    if let Some(pos) = api::position_js(&mouse_chrs.borrow(), bp) {
+                        Callbacks::click(&callbacks, &pos);
+                    }
+                }
+
+                let mut selection = state.selection.lock().unwrap();
+
+                // shift adds to the selection, or toggles the clicked
+                // record, rather than replacing it
+                if clicked {
+                    let hit = match &picking {
+                        Some(target) => {
+                            let frame =
+                                pick_frame(&datasets, &last_tracks, view, bp_per_pixel, mouse_pos);
+                            let picked = target.picked(&frame);
+                            picked_record(&datasets, &chr_offsets, bp_per_pixel, picked)
+                        }
+                        None => nearest_record(
+                            &datasets,
+                            &last_tracks,
+                            &chr_ranges,
+                            &view,
+                            bp_per_pixel,
+                            to_clip(mouse_pos, &sc_desc),
+                        ),
+                    };
+
+                    let selected = hit.map(|(dataset, hit)| SelectedRecord {
+                        dataset: dataset.id,
+                        index: hit.index,
+                        record: hit.record.into_owned(),
+                    });
+
+                    match selected {
+                        Some(selected) if modifiers.shift() => selection.toggle(selected),
+                        Some(selected) => {
+                            selection.clear();
+                            selection.insert(selected);
+                        }
+                        None if modifiers.shift() => (),
+                        None => selection.clear(),
+                    }
+                } else {
+                    if !modifiers.shift() {
+                        selection.clear();
+                    }
+
+                    let (a, b) = (to_clip(start, &sc_desc), to_clip(mouse_pos, &sc_desc));
+
+                    for (dataset, track) in datasets.iter().zip(last_tracks.iter()) {
+                        let inside = dataset.data.records_in_rect(
+                            &chr_ranges,
+                            &view,
+                            track,
+                            bp_per_pixel,
+                            a,
+                            b,
+                        );
+
+                        for (index, record) in inside {
+                            selection.insert(SelectedRecord {
+                                dataset: dataset.id,
+                                index,
+                                record: record.into_owned(),
+                            });
+                        }
+                    }
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::ModifiersChanged(new_modifiers),
+                ..
+            } => modifiers = new_modifiers,
             Event::WindowEvent {
                 event: WindowEvent::MouseWheel { delta, .. },
                 ..
             } => {
+                flight = None;
+
                 let mut view = state.view.load();
 
-                match delta {
-                    winit::event::MouseScrollDelta::LineDelta(_, y) => {
-                        let delta = 1.00 + (-y / 100.0);
-                        view.scale *= delta;
-                    }
-                    winit::event::MouseScrollDelta::PixelDelta(p) => {
-                        let delta = 1.00 + (-p.y / 1000.0) as f32;
-                        view.scale *= delta;
+                let delta = match delta {
+                    winit::event::MouseScrollDelta::LineDelta(_, y) => 1.00 + (-y / 100.0),
+                    winit::event::MouseScrollDelta::PixelDelta(p) => 1.00 + (-p.y / 1000.0) as f32,
+                };
+
+                if modifiers.shift() {
+                    // zoom the y-axis around the -log10(p) under the
+                    // mouse, in whichever track it's over
+                    let mouse_pos = state.mouse_pos.load();
+                    let y = 1.0 - 2.0 * mouse_pos.y / sc_desc.height as f32;
+
+                    if let Some(track) = last_tracks.iter().find(|t| t.bottom <= y && y <= t.top())
+                    {
+                        let range = view.y_range.unwrap_or((track.min_y, track.max_y));
+                        let around = track.value_at(y);
+                        view.y_range = Some(View::zoom_y(range, around, delta));
                     }
+                } else {
+                    view.scale *= delta;
                 }
+
                 state.view.store(view);
             }
             Event::WindowEvent {
@@ -342,15 +1128,268 @@ async fn run(event_loop: EventLoop<()>, window: Window) {
     });
 }
 
+/// The clip space coordinates of the screen space point `pos`.
+fn to_clip(pos: Point, sc_desc: &wgpu::SwapChainDescriptor) -> (f32, f32) {
+    (
+        2.0 * pos.x / sc_desc.width as f32 - 1.0,
+        1.0 - 2.0 * pos.y / sc_desc.height as f32,
+    )
+}
+
+/// The record drawn nearest to the clip space point `point` over
+/// all tracks, and the dataset it's from, if any is close enough to
+/// be under the mouse.
+fn nearest_record<'a>(
+    datasets: &'a [Dataset],
+    tracks: &[TrackParams],
+    chr_ranges: &[(String, (usize, usize))],
+    view: &View,
+    bp_per_pixel: f32,
+    point: (f32, f32),
+) -> Option<(&'a Dataset, RecordHit<'a>)> {
+    datasets
+        .iter()
+        .zip(tracks.iter())
+        .filter_map(|(dataset, track)| {
+            let hit = dataset.data.nearest_record(
+                chr_ranges,
+                view,
+                track,
+                bp_per_pixel,
+                point,
+                gwas::MARKER_SIZE,
+            )?;
+            Some((dataset, hit))
+        })
+        .min_by(|(_, a), (_, b)| a.distance.partial_cmp(&b.distance).unwrap())
+}
+
+/// Whether GPU picking can be used if it's `enabled`, which it can't
+/// if the coordinate system has more chromosomes than the picking
+/// target can tell apart.
+fn can_pick_on_gpu(enabled: bool, chr_count: usize) -> bool {
+    if enabled && chr_count > gwas::picking::MAX_PICK_CHRS {
+        let msg = format!(
+            "GPU picking is off, as it can tell at most {} chromosomes apart, not {}",
+            gwas::picking::MAX_PICK_CHRS,
+            chr_count
+        );
+        web_sys::console::warn_1(&msg.into());
+        return false;
+    }
+
+    enabled
+}
+
+/// How the picking target is drawn with `view` and `tracks`, and
+/// the pixel of it under the mouse.
+fn pick_frame(
+    datasets: &[Dataset],
+    tracks: &[TrackParams],
+    view: View,
+    bp_per_pixel: f32,
+    mouse_pos: Point,
+) -> PickFrame {
+    PickFrame {
+        pixel: (mouse_pos.x.max(0.0) as u32, mouse_pos.y.max(0.0) as u32),
+        view,
+        bp_per_pixel,
+        tracks: tracks.to_vec(),
+        datasets: datasets
+            .iter()
+            .map(|d| (d.id, d.data.record_count()))
+            .collect(),
+    }
+}
+
+/// The record drawn at the pixel read back from the picking target,
+/// and the dataset it's from.
+fn picked_record<'a>(
+    datasets: &'a [Dataset],
+    chr_offsets: &[(String, usize)],
+    bp_per_pixel: f32,
+    picked: Option<PickId>,
+) -> Option<(&'a Dataset, RecordHit<'a>)> {
+    let picked = picked?;
+
+    let dataset = datasets.iter().find(|d| d.id == picked.dataset)?;
+    let (chr, _) = chr_offsets.get(picked.chr_ix)?;
+
+    let index = dataset
+        .data
+        .record_for_instance(chr, bp_per_pixel, picked.instance)?;
+    let record = dataset.data.record(chr, index)?;
+
+    Some((
+        dataset,
+        RecordHit {
+            distance: 0.0,
+            index,
+            record,
+        },
+    ))
+}
+
+/// Renames the chromosomes of `batch` to the coordinate system's
+/// names for them, which is what they're looked up by when drawn,
+/// setting aside the records of those it doesn't have in `unknown`.
+/// Returns `None` if none of the batch is left.
+fn resolve_chrs(
+    coords: &CoordinateSystem,
+    unknown: &mut HashMap<String, Vec<RecordBatch>>,
+    batch: RecordBatch,
+) -> Option<RecordBatch> {
+    match batch {
+        RecordBatch::Records(records) => {
+            let mut known = Vec::with_capacity(records.len());
+            let mut unknown_records: HashMap<String, Vec<GwasRecord>> = HashMap::default();
+
+            for mut record in records {
+                match coords.find_chr(&record.chr) {
+                    Some(chr) => {
+                        if record.chr != chr {
+                            record.chr = chr.to_string();
+                        }
+                        known.push(record);
+                    }
+                    None => unknown_records
+                        .entry(record.chr.clone())
+                        .or_default()
+                        .push(record),
+                }
+            }
+
+            for (chr, records) in unknown_records {
+                unknown
+                    .entry(chr)
+                    .or_default()
+                    .push(RecordBatch::Records(records));
+            }
+
+            Some(RecordBatch::Records(known))
+        }
+        RecordBatch::Columns(mut chr) => match coords.find_chr(&chr.name) {
+            Some(name) => {
+                if chr.name != name {
+                    chr.name = name.to_string().into();
+                }
+                Some(RecordBatch::Columns(chr))
+            }
+            None => {
+                let name = chr.name.to_string();
+                unknown
+                    .entry(name)
+                    .or_default()
+                    .push(RecordBatch::Columns(chr));
+                None
+            }
+        },
+    }
+}
+
+/// Adds the records of `dataset` that were set aside as being on
+/// chromosomes missing from the coordinate system, but that it now
+/// has, e.g. as an alias has been added. Returns the names the
+/// dataset gave those chromosomes.
+fn resolve_unknown_chrs(
+    coords: &CoordinateSystem,
+    dataset: &mut Dataset,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+) -> Vec<String> {
+    let resolved: Vec<String> = dataset
+        .unknown_chrs
+        .keys()
+        .filter(|chr| coords.find_chr(chr).is_some())
+        .cloned()
+        .collect();
+
+    for chr in resolved.iter() {
+        for batch in dataset.unknown_chrs.remove(chr).unwrap_or_default() {
+            let mut unknown = HashMap::default();
+
+            if let Some(batch) = resolve_chrs(coords, &mut unknown, batch) {
+                dataset.data.append_batch(device, queue, batch);
+            }
+        }
+    }
+
+    resolved
+}
+
+/// Warns if a dataset states a genome assembly other than that of
+/// the coordinate system.
+fn check_assembly(coords: &CoordinateSystem, dataset: &Dataset) {
+    if let Some(assembly) = &dataset.data.assembly {
+        if !coords.matches_assembly(assembly) {
+            let msg = format!(
+                "{} uses assembly {}, but the coordinate system is {}",
+                dataset.label,
+                assembly,
+                coords.name()
+            );
+            web_sys::console::warn_1(&msg.into());
+        }
+    }
+}
+
+/// Set once `main` or `start` has started the browser, as each would
+/// otherwise add another canvas and event loop to the page.
+static LAUNCHED: AtomicBool = AtomicBool::new(false);
+
+/// Starts the browser with the configuration given by the query
+/// string of the page, falling back to the defaults. Does nothing if
+/// the page already started it with `start`.
 #[wasm_bindgen]
 pub fn main() {
-    let event_loop = EventLoop::new();
+    if !LAUNCHED.swap(true, Ordering::SeqCst) {
+        launch(AppConfig::from_query());
+    }
+}
+
+/// Starts the browser with a configuration object, see
+/// `AppConfig::apply_js` for its fields. Fields it leaves out are
+/// taken from the query string of the page, or the defaults.
+///
+/// Pages using this should call it instead of `main`; it's an error
+/// to call it once the browser has been started.
+///
+/// Returns a handle for controlling the browser from the page.
+#[wasm_bindgen]
+pub fn start(config: JsValue) -> Result<Browser, JsValue> {
+    let mut app_config = AppConfig::from_query();
+    app_config.apply_js(&config)?;
+
+    if LAUNCHED.swap(true, Ordering::SeqCst) {
+        return Err(JsValue::from_str("the browser has already been started"));
+    }
+
+    Ok(launch(app_config))
+}
+
+fn launch(mut config: AppConfig) -> Browser {
+    let event_loop = EventLoop::with_user_event();
+
+    let (mut browser, shared) = Browser::new(event_loop.create_proxy());
+
+    // sent first, so that they apply to the datasets and the region
+    for (alias, chr) in std::mem::take(&mut config.chr_aliases) {
+        browser.add_chr_alias(alias, chr);
+    }
+
+    if let Some(region) = config.region.take() {
+        browser.show_region(region);
+    }
+
+    for dataset in std::mem::take(&mut config.datasets) {
+        browser.load(dataset);
+    }
     /*
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
         // Temporarily avoid srgb formats for the swapchain on the web
-        pollster::block_on(run(event_loop, window));
+        pollster::block_on(run(event_loop, window, config, shared));
     }
     */
     #[cfg(target_arch = "wasm32")]
@@ -378,6 +1417,8 @@ pub fn main() {
                     .ok()
             })
             .expect("couldn't append canvas to document body");
-        wasm_bindgen_futures::spawn_local(run(event_loop, window));
+        wasm_bindgen_futures::spawn_local(run(event_loop, window, config, shared));
     }
+
+    browser
 }
@@ -0,0 +1,133 @@
+//! Resolving what's typed into the search box to a place in the
+//! genome: a variant by its rs ID, a locus, or a gene by its symbol.
+
+use crate::config::Region;
+use crate::coordinates::CoordinateSystem;
+use crate::genes::GeneIndex;
+use crate::gwas::GwasDataChrs;
+use crate::selection::SelectedRecord;
+
+/// The narrowest region a search shows, so that a single variant is
+/// shown with its surroundings.
+pub const MIN_WIDTH: usize = 100_000;
+
+/// Where a search leads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// The region to show, on a chromosome as the coordinate system
+    /// names it.
+    pub region: Region,
+    /// The variant that was searched for, if any.
+    pub record: Option<SelectedRecord>,
+}
+
+impl SearchHit {
+    /// The range of the chromosome the hit is on to show, widened to
+    /// `MIN_WIDTH` around its center if it's narrower.
+    pub fn range(&self, chr_len: usize) -> (usize, usize) {
+        let (start, end) = self.region.range.unwrap_or((0, chr_len));
+
+        if end - start >= MIN_WIDTH {
+            return (start, end);
+        }
+
+        let center = (start + end) / 2;
+        let start = center.saturating_sub(MIN_WIDTH / 2);
+        (start, start + MIN_WIDTH)
+    }
+}
+
+/// Looks for `query`, in turn, as the rs ID of a loaded record, a
+/// locus on a chromosome of `coords`, and the symbol of a gene.
+/// `datasets` are the loaded datasets with their IDs.
+pub fn search<'a>(
+    query: &str,
+    coords: &CoordinateSystem,
+    datasets: impl IntoIterator<Item = (usize, &'a GwasDataChrs)>,
+    genes: &GeneIndex,
+) -> Result<SearchHit, String> {
+    let query = query.trim();
+
+    if query.is_empty() {
+        return Err("Type an rs ID, a locus or a gene".to_string());
+    }
+
+    if is_rs_id(query) {
+        return find_rs(query, coords, datasets)
+            .ok_or_else(|| format!("No loaded variant is called {}", query));
+    }
+
+    if let Some(locus) = coords.parse_locus(query) {
+        return Ok(SearchHit {
+            region: Region {
+                chr: locus.chr,
+                range: Some((locus.start, locus.end)),
+            },
+            record: None,
+        });
+    }
+
+    if let Some(gene) = genes.find(query) {
+        let chr = coords
+            .find_chr(&gene.chr)
+            .ok_or_else(|| format!("{} is on unknown chromosome {}", gene.name, gene.chr))?;
+
+        return Ok(SearchHit {
+            region: Region {
+                chr: chr.to_string(),
+                range: Some((gene.start, gene.end.max(gene.start + 1))),
+            },
+            record: None,
+        });
+    }
+
+    Err(format!("Nothing found for {}", query))
+}
+
+/// Whether `text` looks like `rs` followed by a number.
+fn is_rs_id(text: &str) -> bool {
+    match (text.get(..2), text.get(2..)) {
+        (Some(prefix), Some(number)) => {
+            prefix.eq_ignore_ascii_case("rs")
+                && !number.is_empty()
+                && number.bytes().all(|b| b.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+/// The first record called `rs`, ignoring case, in the first dataset
+/// that has one.
+fn find_rs<'a>(
+    rs: &str,
+    coords: &CoordinateSystem,
+    datasets: impl IntoIterator<Item = (usize, &'a GwasDataChrs)>,
+) -> Option<SearchHit> {
+    for (dataset, data) in datasets {
+        for records in data.data.values() {
+            let found = (0..records.len()).find(|&ix| {
+                records
+                    .rs(ix)
+                    .map_or(false, |name| name.eq_ignore_ascii_case(rs))
+            });
+
+            if let Some((index, record)) = found.and_then(|ix| Some((ix, records.get(ix)?))) {
+                let chr = coords.find_chr(&record.chr).unwrap_or(&record.chr);
+
+                return Some(SearchHit {
+                    region: Region {
+                        chr: chr.to_string(),
+                        range: Some((record.pos, record.pos + 1)),
+                    },
+                    record: Some(SelectedRecord {
+                        dataset,
+                        index,
+                        record: record.into_owned(),
+                    }),
+                });
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,109 @@
+//! The records the user has picked out, which are drawn larger, listed
+//! in a panel, and handed to the host page.
+
+use crate::gwas::GwasRecord;
+
+/// A selected record, identified by the dataset it's from and its
+/// index among the records of its chromosome.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedRecord {
+    /// The ID of the dataset.
+    pub dataset: usize,
+    pub index: usize,
+    pub record: GwasRecord,
+}
+
+impl SelectedRecord {
+    fn is(&self, dataset: usize, chr: &str, index: usize) -> bool {
+        self.dataset == dataset && self.index == index && self.record.chr == chr
+    }
+}
+
+/// The selected records, in the order they were selected.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Selection {
+    records: Vec<SelectedRecord>,
+
+    /// Incremented whenever the selection changes, so that whatever
+    /// depends on it can tell when it's out of date.
+    generation: usize,
+}
+
+impl Selection {
+    pub fn records(&self) -> &[SelectedRecord] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn contains(&self, dataset: usize, chr: &str, index: usize) -> bool {
+        self.records.iter().any(|r| r.is(dataset, chr, index))
+    }
+
+    /// Adds `selected`, unless it's already selected.
+    pub fn insert(&mut self, selected: SelectedRecord) {
+        if !self.contains(selected.dataset, &selected.record.chr, selected.index) {
+            self.records.push(selected);
+            self.generation += 1;
+        }
+    }
+
+    /// Adds `selected` if it isn't selected, and removes it if it is.
+    pub fn toggle(&mut self, selected: SelectedRecord) {
+        let len = self.records.len();
+        let (dataset, index) = (selected.dataset, selected.index);
+
+        self.records
+            .retain(|r| !r.is(dataset, &selected.record.chr, index));
+
+        if self.records.len() == len {
+            self.records.push(selected);
+        }
+
+        self.generation += 1;
+    }
+
+    /// Removes the `ix`th selected record.
+    pub fn remove(&mut self, ix: usize) {
+        if ix < self.records.len() {
+            self.records.remove(ix);
+            self.generation += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        if !self.records.is_empty() {
+            self.records.clear();
+            self.generation += 1;
+        }
+    }
+
+    /// Removes the records of the dataset with the ID `dataset`.
+    pub fn remove_dataset(&mut self, dataset: usize) {
+        let len = self.records.len();
+        self.records.retain(|r| r.dataset != dataset);
+
+        if self.records.len() != len {
+            self.generation += 1;
+        }
+    }
+
+    /// The chromosomes and indices of the selected records of the
+    /// dataset with the ID `dataset`.
+    pub fn of_dataset(&self, dataset: usize) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.records
+            .iter()
+            .filter(move |r| r.dataset == dataset)
+            .map(|r| (r.record.chr.as_str(), r.index))
+    }
+}
@@ -1,11 +1,15 @@
 use crate::geometry::{Point, Rect};
+use crate::selection::Selection;
 use crate::view::{View, ViewportDims};
 
 use crossbeam::atomic::AtomicCell;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+#[derive(Default, Clone)]
 pub struct SharedState {
     pub view: Arc<AtomicCell<View>>,
 
     pub mouse_pos: Arc<AtomicCell<Point>>,
+
+    pub selection: Arc<Mutex<Selection>>,
 }
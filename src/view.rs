@@ -8,6 +8,10 @@ pub struct View {
 
     pub center: f32,
     pub scale: f32,
+
+    /// The -log10(p) range shown along the tracks, or `None` to
+    /// leave it to the y-axis mode.
+    pub y_range: Option<(f32, f32)>,
 }
 
 impl View {
@@ -34,6 +38,79 @@ impl View {
         scaling * translation
     }
 
+    /// `to_scaled_matrix`, preceded by mapping the y-coordinates in
+    /// `from` to the clip space y-coordinates in `to`.
+    #[rustfmt::skip]
+    pub fn to_track_matrix(&self, from: (f32, f32), to: (f32, f32)) -> glm::Mat4 {
+        let span = from.1 - from.0;
+        let s = if span.abs() > f32::EPSILON { (to.1 - to.0) / span } else { 0.0 };
+        let y = to.0 - from.0 * s;
+
+        let y_map =
+            glm::mat4(1.0, 0.0, 0.0, 0.0,
+                      0.0,   s, 0.0,   y,
+                      0.0, 0.0, 1.0, 0.0,
+                      0.0, 0.0, 0.0, 1.0);
+
+        self.to_scaled_matrix() * y_map
+    }
+
+    /// The view `t` of the way from `self` to `other`, zooming at a
+    /// constant rate. The y-range is that of `other`.
+    pub fn lerp(&self, other: &View, t: f32) -> View {
+        let t = t.clamp(0.0, 1.0);
+
+        View {
+            center: self.center + (other.center - self.center) * t,
+            scale: self.scale * (other.scale / self.scale).powf(t),
+            ..*other
+        }
+    }
+
+    /// `range` zoomed by `factor` around the -log10(p) `around`,
+    /// keeping it above 0.
+    pub fn zoom_y(range: (f32, f32), around: f32, factor: f32) -> (f32, f32) {
+        let lo = around - (around - range.0) * factor;
+        let hi = around + (range.1 - around) * factor;
+
+        Self::clamp_y((lo, hi))
+    }
+
+    /// `range` moved by `fraction` of its length, keeping it above 0.
+    pub fn pan_y(range: (f32, f32), fraction: f32) -> (f32, f32) {
+        let delta = (range.1 - range.0) * fraction;
+        Self::clamp_y((range.0 + delta, range.1 + delta))
+    }
+
+    fn clamp_y((lo, hi): (f32, f32)) -> (f32, f32) {
+        let len = (hi - lo).max(0.1);
+
+        if lo < 0.0 {
+            (0.0, len)
+        } else {
+            (lo, lo + len)
+        }
+    }
+
+    /// The genome-wide basepair at horizontal pixel `x` of a
+    /// viewport that's `width` pixels wide.
+    pub fn x_to_bp(&self, x: f32, width: f32) -> f32 {
+        let ndc = 2.0 * (x / width) - 1.0;
+        self.center + ndc * self.scale / self.base_bp_width
+    }
+
+    /// The number of basepairs each pixel of a viewport that's
+    /// `width` pixels wide covers.
+    pub fn bp_per_pixel(&self, width: f32) -> f32 {
+        2.0 * self.scale / (self.base_bp_width * width)
+    }
+
+    /// The genome-wide basepair range that's in view.
+    pub fn bp_range(&self) -> (f32, f32) {
+        let radius = self.scale / self.base_bp_width;
+        (self.center - radius, self.center + radius)
+    }
+
     pub fn basepair_to_screen_map(&self) -> glm::Mat4 {
         let s = self.base_bp_width / self.scale;
         let x = self.center;
@@ -78,6 +155,7 @@ impl Default for View {
             center: 500_000.0,
             // scale: 1.0,
             scale: 10_000.0,
+            y_range: None,
         }
     }
 }
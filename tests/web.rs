@@ -11,3 +11,837 @@ wasm_bindgen_test_configure!(run_in_browser);
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+mod gemma {
+    use super::*;
+
+    use rust_genetics_browser::gwas::table::{parse_gemma, GemmaPValue};
+
+    const ASSOC: &str = "\
+chr\trs\tps\tn_miss\tallele1\tallele0\taf\tbeta\tse\tlogl_H1\tl_remle\tl_mle\tp_wald\tp_lrt\tp_score
+1\trs1\t1000\t0\tA\tG\t0.25\t0.5\t0.1\t-100\t1\t1\t1e-8\t1e-6\t1e-4
+2\trs2\t2000\t0\tC\tT\t0.5\t-0.2\t0.1\t-100\t1\t1\t1e-300\t0.1\t1
+";
+
+    fn neg_log10_ps(p_value: GemmaPValue) -> Vec<f64> {
+        parse_gemma(ASSOC, p_value)
+            .unwrap()
+            .iter()
+            .map(|record| record.neg_log10_p)
+            .collect()
+    }
+
+    fn approx_eq(a: &[f64], b: &[f64]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-9)
+    }
+
+    #[wasm_bindgen_test]
+    fn fields() {
+        let records = parse_gemma(ASSOC, GemmaPValue::Wald).unwrap();
+
+        assert_eq!(records.len(), 2);
+
+        let record = &records[0];
+        assert_eq!(record.chr, "1");
+        assert_eq!(record.pos, 1000);
+        assert_eq!(record.rs.as_deref(), Some("rs1"));
+        assert_eq!(record.allele1.as_deref(), Some("A"));
+        assert_eq!(record.allele0.as_deref(), Some("G"));
+        assert_eq!(record.af, Some(0.25));
+        assert_eq!(record.beta, Some(0.5));
+        assert_eq!(record.se, Some(0.1));
+    }
+
+    #[wasm_bindgen_test]
+    fn p_value_column() {
+        assert!(approx_eq(&neg_log10_ps(GemmaPValue::Wald), &[8.0, 300.0]));
+        assert!(approx_eq(&neg_log10_ps(GemmaPValue::Lrt), &[6.0, 1.0]));
+        assert!(approx_eq(&neg_log10_ps(GemmaPValue::Score), &[4.0, 0.0]));
+    }
+
+    #[wasm_bindgen_test]
+    fn lrt_without_effect_size() {
+        let text = "\
+chr\trs\tps\tn_mis\tn_obs\tallele1\tallele0\taf\tlogl_H1\tl_mle\tp_lrt
+1\trs1\t1000\t0\t100\tA\tG\t0.25\t-100\t1\t1e-6
+";
+
+        let records = parse_gemma(text, GemmaPValue::Lrt).unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].beta, None);
+        assert!((records[0].neg_log10_p - 6.0).abs() < 1e-9);
+    }
+
+    #[wasm_bindgen_test]
+    fn missing_p_value_column() {
+        let text = "chr\trs\tps\tp_wald\n1\trs1\t1000\t0.5\n";
+        let err = parse_gemma(text, GemmaPValue::Lrt).unwrap_err();
+
+        assert_eq!(err.to_string(), "missing column `p_lrt` for p-value");
+    }
+
+    #[wasm_bindgen_test]
+    fn zero_p_value() {
+        let text = "\
+chr\trs\tps\tn_mis\tn_obs\tallele1\tallele0\taf\tlogl_H1\tl_mle\tp_lrt
+1\trs1\t1000\t0\t100\tA\tG\t0.25\t-100\t1\t0
+1\trs2\t2000\t0\t100\tC\tT\t0.5\t-100\t1\t1e-400
+";
+
+        let records = parse_gemma(text, GemmaPValue::Lrt).unwrap();
+
+        assert!((records[0].neg_log10_p - 323.306).abs() < 1e-3);
+        assert!((records[1].neg_log10_p - 400.0).abs() < 1e-9);
+    }
+}
+
+mod plink {
+    use super::*;
+
+    use rust_genetics_browser::gwas::table::parse_plink;
+
+    #[wasm_bindgen_test]
+    fn plink_1() {
+        let text = "\
+ CHR         SNP         BP   A1      TEST    NMISS       BETA         STAT            P
+   1         rs1       1000    A       ADD      100      0.5          5.0        1e-05
+   1         rs1       1000    A       COV1     100      0.1          1.0         0.3
+   2         rs2       2000    C       ADD      100     -0.2         -1.0          0.1
+";
+
+        let records = parse_plink(text).unwrap();
+
+        // only the additive rows are kept
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].rs.as_deref(), Some("rs1"));
+        assert_eq!(records[0].beta, Some(0.5));
+        assert!((records[0].neg_log10_p - 5.0).abs() < 1e-9);
+        assert_eq!(records[1].chr, "2");
+        assert_eq!(records[1].pos, 2000);
+    }
+
+    #[wasm_bindgen_test]
+    fn plink_2_log10() {
+        let text = "\
+#CHROM\tPOS\tID\tREF\tALT\tA1\tTEST\tOBS_CT\tBETA\tSE\tT_STAT\tLOG10_P
+1\t1000\trs1\tG\tA\tA\tADD\t100\t0.5\t0.1\t5.0\t12.5
+1\t1000\trs1\tG\tA\tA\tDOMDEV\t100\t0.1\t0.1\t1.0\t0.5
+X\t3000\trs3\tC\tT\tT\tADD\t100\t0.2\t0.1\t2.0\tNA
+";
+
+        let records = parse_plink(text).unwrap();
+
+        // the row without a p-value is left out
+        assert_eq!(records.len(), 1);
+
+        let record = &records[0];
+        assert_eq!(record.chr, "1");
+        assert_eq!(record.pos, 1000);
+        assert_eq!(record.se, Some(0.1));
+        assert_eq!(record.neg_log10_p, 12.5);
+    }
+
+    #[wasm_bindgen_test]
+    fn missing_p_value_column() {
+        let text = "CHR\tSNP\tBP\tBETA\n1\trs1\t1000\t0.5\n";
+        let err = parse_plink(text).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "missing column `P` or `LOG10_P` for p-value"
+        );
+    }
+}
+
+mod column_mapping {
+    use super::*;
+
+    use rust_genetics_browser::gwas::table::{
+        parse_mapped, ColumnMapping, Delimiter, PValueColumn,
+    };
+
+    fn mapping() -> ColumnMapping {
+        ColumnMapping {
+            delimiter: Delimiter::Comma,
+            rs: Some("snp".into()),
+            beta: Some("effect".into()),
+            ..ColumnMapping::new("chrom", "position", PValueColumn::P("pval".into()))
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn mapped() {
+        let text = "snp,chrom,position,effect,pval\nrs1,3,1500,0.25,0.001\nrs2,3,2500,NA,0.5\n";
+        let records = parse_mapped(text, &mapping()).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].chr, "3");
+        assert_eq!(records[0].pos, 1500);
+        assert_eq!(records[0].rs.as_deref(), Some("rs1"));
+        assert_eq!(records[0].beta, Some(0.25));
+        assert!((records[0].neg_log10_p - 3.0).abs() < 1e-9);
+        assert_eq!(records[1].beta, None);
+    }
+
+    #[wasm_bindgen_test]
+    fn missing_column() {
+        let text = "snp,chrom,effect,pval\nrs1,3,0.25,0.001\n";
+        let err = parse_mapped(text, &mapping()).unwrap_err();
+
+        assert_eq!(err.to_string(), "missing column `position` for position");
+    }
+
+    #[wasm_bindgen_test]
+    fn missing_optional_column() {
+        // a misspelt optional column is an error rather than left empty
+        let text = "snp,chrom,position,efect,pval\nrs1,3,1500,0.25,0.001\n";
+        let err = parse_mapped(text, &mapping()).unwrap_err();
+
+        assert_eq!(err.to_string(), "missing column `effect` for effect size");
+    }
+
+    #[wasm_bindgen_test]
+    fn missing_p_value_column() {
+        let mapping = ColumnMapping {
+            p: PValueColumn::NegLog10P("mlog10p".into()),
+            ..mapping()
+        };
+
+        let text = "snp,chrom,position,effect,pval\nrs1,3,1500,0.25,0.001\n";
+        let err = parse_mapped(text, &mapping).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "missing column `mlog10p` for -log10 p-value"
+        );
+    }
+}
+
+mod formats {
+    use super::*;
+
+    use rust_genetics_browser::gwas::{GemmaPValue, GwasFormat};
+
+    #[wasm_bindgen_test]
+    fn from_url() {
+        let ssf = "https://ftp.ebi.ac.uk/GCST90000123/harmonised/GCST90000123.h.tsv.gz";
+        assert_eq!(GwasFormat::from_url(ssf), GwasFormat::Ssf);
+
+        // other tab-separated files aren't assumed to be GWAS-SSF
+        assert_eq!(GwasFormat::from_url("results.tsv"), GwasFormat::Json);
+
+        assert_eq!(
+            GwasFormat::from_url("https://bucket.example/out.assoc.txt.gz?X-Sig=abc.tsv#top"),
+            GwasFormat::Gemma(GemmaPValue::Wald)
+        );
+        assert_eq!(
+            GwasFormat::from_url("/data/trait.PHENO1.glm.linear?v=2"),
+            GwasFormat::Plink
+        );
+        assert_eq!(GwasFormat::from_url("data.gwb#chr1"), GwasFormat::Binary);
+    }
+}
+
+mod decompress {
+    use super::*;
+
+    use std::io::Write;
+
+    use rust_genetics_browser::fetch::{decompress, StreamDecoder};
+
+    /// `text` as a series of gzip members, one per part, the way
+    /// `bgzip` writes it in blocks.
+    pub fn bgzf(parts: &[&str]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        for part in parts {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(part.as_bytes()).unwrap();
+            bytes.extend(encoder.finish().unwrap());
+        }
+
+        bytes
+    }
+
+    #[wasm_bindgen_test]
+    fn plain() {
+        let bytes = b"chr\tps\n".to_vec();
+        assert_eq!(decompress(bytes.clone()).unwrap(), bytes);
+    }
+
+    #[wasm_bindgen_test]
+    fn multi_member() {
+        let bytes = bgzf(&["chr\tps\n", "1\t1000\n", "2\t2000\n"]);
+        let text = decompress(bytes).unwrap();
+
+        assert_eq!(text, b"chr\tps\n1\t1000\n2\t2000\n".to_vec());
+    }
+
+    #[wasm_bindgen_test]
+    fn streamed() {
+        let bytes = bgzf(&["chr\tps\n", "1\t1000\n", "2\t2000\n"]);
+
+        // chunks smaller than the gzip magic number, and ones that
+        // straddle the members
+        for size in &[1, 7, 40] {
+            let mut decoder = StreamDecoder::default();
+            let mut text = Vec::new();
+
+            for chunk in bytes.chunks(*size) {
+                text.extend(decoder.push(chunk).unwrap());
+            }
+
+            text.extend(decoder.finish().unwrap());
+            assert_eq!(text, b"chr\tps\n1\t1000\n2\t2000\n".to_vec());
+        }
+
+        let mut decoder = StreamDecoder::default();
+        let mut text = decoder.push(b"chr\tps\n").unwrap();
+        text.extend(decoder.finish().unwrap());
+
+        assert_eq!(text, b"chr\tps\n".to_vec());
+    }
+
+    #[wasm_bindgen_test]
+    fn truncated() {
+        let mut bytes = bgzf(&["chr\tps\n1\t1000\n"]);
+        bytes.truncate(bytes.len() - 10);
+
+        assert!(decompress(bytes).is_err());
+    }
+}
+
+mod binary {
+    use super::*;
+
+    use rust_genetics_browser::gwas::binary::{decode, encode, Precision, MAGIC};
+    use rust_genetics_browser::gwas::GwasRecord;
+
+    fn records(with_rsids: bool) -> Vec<GwasRecord> {
+        let mut records = vec![
+            GwasRecord::new("2", 3000, 1.5),
+            GwasRecord::new("1", 2000, 7.25),
+            GwasRecord::new("2", 1000, 0.125),
+            GwasRecord::new("1", 1000, 3.0),
+        ];
+
+        if with_rsids {
+            records[0].rs = Some("rs3".into());
+            records[1].rs = Some("rs2".into());
+            records[3].rs = Some("rs1".into());
+        }
+
+        records
+    }
+
+    fn round_trip(precision: Precision, with_rsids: bool) {
+        let bytes = encode(&records(with_rsids), precision).unwrap();
+        let chrs = decode(&bytes).unwrap();
+
+        // in the order the chromosomes first appear, sorted by position
+        let names: Vec<&str> = chrs.iter().map(|chr| chr.name.as_ref()).collect();
+        assert_eq!(names, vec!["2", "1"]);
+        assert_eq!(chrs[0].positions.to_vec(), vec![1000, 3000]);
+        assert_eq!(chrs[1].positions.to_vec(), vec![1000, 2000]);
+
+        let decoded: Vec<GwasRecord> = chrs.iter().flat_map(|chr| chr.records()).collect();
+        assert_eq!(decoded.len(), 4);
+        assert_eq!(decoded[1].neg_log10_p, 1.5);
+        assert_eq!(decoded[3].neg_log10_p, 7.25);
+
+        if with_rsids {
+            assert_eq!(chrs[0].rsid(0), None);
+            assert_eq!(decoded[1].rs.as_deref(), Some("rs3"));
+            assert_eq!(decoded[2].rs.as_deref(), Some("rs1"));
+        } else {
+            assert!(decoded.iter().all(|record| record.rs.is_none()));
+        }
+
+        // and the same once the columns no longer borrow the bytes
+        let owned = chrs[1].clone().into_owned();
+        drop(bytes);
+        assert_eq!(owned.record(1).pos, 2000);
+    }
+
+    #[wasm_bindgen_test]
+    fn round_trip_f32() {
+        round_trip(Precision::F32, false);
+        round_trip(Precision::F32, true);
+    }
+
+    #[wasm_bindgen_test]
+    fn round_trip_f64() {
+        round_trip(Precision::F64, false);
+        round_trip(Precision::F64, true);
+
+        let mut records = records(false);
+        records[0].neg_log10_p = 123.456789012345;
+
+        let bytes = encode(&records, Precision::F64).unwrap();
+        let chrs = decode(&bytes).unwrap();
+        assert_eq!(chrs[0].neg_log10_p.get(1), 123.456789012345);
+    }
+
+    #[wasm_bindgen_test]
+    fn truncated() {
+        let bytes = encode(&records(true), Precision::F32).unwrap();
+
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err(), "truncated to {}", len);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn corrupt_header() {
+        assert!(decode(b"GWB2\0\0\0\0\0\0\0\0").is_err());
+
+        // a chromosome count far larger than the file could hold
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode(&bytes).is_err());
+
+        // a record count whose columns would overflow their length
+        let mut bytes = encode(&records(true), Precision::F64).unwrap();
+        bytes[24..28].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode(&bytes).is_err());
+
+        // as would a name length
+        let mut bytes = encode(&records(false), Precision::F32).unwrap();
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode(&bytes).is_err());
+    }
+}
+
+mod table_reader {
+    use super::*;
+
+    use rust_genetics_browser::gwas::table::{
+        ColumnMapping, Delimiter, PValueColumn, TableLayout, TableReader,
+    };
+    use rust_genetics_browser::gwas::GwasRecord;
+
+    const TEXT: &str = "chr\tps\tp\tgene\n1\t1000\t0.01\tα-globin\n2\t2000\t0.5\tβ-globin\n";
+
+    fn layout() -> TableLayout {
+        TableLayout::Mapped(ColumnMapping {
+            delimiter: Delimiter::Tab,
+            annotation: Some("gene".into()),
+            ..ColumnMapping::new("chr", "ps", PValueColumn::P("p".into()))
+        })
+    }
+
+    fn read(reader: &mut TableReader, chunks: &[&[u8]]) -> Vec<GwasRecord> {
+        let mut records = Vec::new();
+
+        for chunk in chunks {
+            records.extend(reader.push(chunk).unwrap());
+        }
+
+        records.extend(reader.finish().unwrap());
+        records
+    }
+
+    #[wasm_bindgen_test]
+    fn lines_split_across_chunks() {
+        let bytes = TEXT.as_bytes();
+
+        // including splits in the middle of the Greek letters
+        for ix in 0..=bytes.len() {
+            let (first, rest) = bytes.split_at(ix);
+            let records = read(&mut TableReader::new(layout()), &[first, rest]);
+
+            assert_eq!(records.len(), 2, "split at {}", ix);
+            assert_eq!(records[0].annotation.as_deref(), Some("α-globin"));
+            assert_eq!(records[1].annotation.as_deref(), Some("β-globin"));
+            assert_eq!(records[1].pos, 2000);
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn byte_at_a_time() {
+        let chunks: Vec<&[u8]> = TEXT.as_bytes().chunks(1).collect();
+        let records = read(&mut TableReader::new(layout()), &chunks);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].annotation.as_deref(), Some("α-globin"));
+    }
+
+    #[wasm_bindgen_test]
+    fn missing_final_newline() {
+        let text = TEXT.trim_end();
+        let records = read(&mut TableReader::new(layout()), &[text.as_bytes()]);
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].annotation.as_deref(), Some("β-globin"));
+    }
+
+    #[wasm_bindgen_test]
+    fn skip_bad_rows() {
+        let text = format!("{}3\t3000\tabc\tγ\n4\n5\t5000\t0.1\tδ\n", TEXT);
+
+        let mut reader = TableReader::new(layout()).skip_bad_rows(true);
+        let records = read(&mut reader, &[text.as_bytes()]);
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].chr, "5");
+        assert_eq!(reader.skipped_rows(), 2);
+
+        let mut reader = TableReader::new(layout());
+        assert!(reader.push(text.as_bytes()).is_err());
+    }
+}
+
+mod json_array {
+    use super::*;
+
+    use rust_genetics_browser::gwas::JsonArraySplitter;
+
+    /// Pushes `chunks` through a splitter, collecting the batches.
+    fn split(chunks: &[&[u8]]) -> Vec<String> {
+        let mut splitter = JsonArraySplitter::default();
+
+        let batches = chunks
+            .iter()
+            .filter_map(|chunk| splitter.push(chunk).unwrap())
+            .collect();
+
+        splitter.finish().unwrap();
+        batches
+    }
+
+    #[wasm_bindgen_test]
+    fn split_elements() {
+        let json = r#"[{"chr":"1","ps":1000}, {"chr":"2","ps":2000}]"#.as_bytes();
+        let (first, rest) = json.split_at(30);
+
+        assert_eq!(
+            split(&[first, rest]),
+            vec![r#"[{"chr":"1","ps":1000}]"#, r#"[{"chr":"2","ps":2000}]"#]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn split_multibyte_character() {
+        let json = r#"[{"annotation":"α-globin"}]"#.as_bytes();
+        let alpha = json.iter().position(|&b| b == 0xce).unwrap();
+        let (first, rest) = json.split_at(alpha + 1);
+
+        assert_eq!(
+            split(&[first, rest]),
+            vec![r#"[{"annotation":"α-globin"}]"#]
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn brackets_in_strings() {
+        let json = r#"[{"rs":"a]}\",\"{"}, {"rs":"b"}, 1, null]"#.as_bytes();
+        let mut splitter = JsonArraySplitter::default();
+
+        assert_eq!(
+            splitter.push(json).unwrap().unwrap(),
+            r#"[{"rs":"a]}\",\"{"},{"rs":"b"},1,null]"#
+        );
+        assert_eq!(splitter.elements(), 4);
+        assert!(splitter.finish().is_ok());
+    }
+
+    #[wasm_bindgen_test]
+    fn truncated() {
+        let mut splitter = JsonArraySplitter::default();
+        splitter.push(br#"[{"chr":"1"}, {"chr""#).unwrap();
+
+        assert!(splitter.finish().is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn not_an_array() {
+        let mut splitter = JsonArraySplitter::default();
+        assert!(splitter.push(br#"{"chr":"1"}"#).is_err());
+
+        let mut splitter = JsonArraySplitter::default();
+        assert!(splitter.push(b"[] []").is_err());
+    }
+}
+
+mod json_reader {
+    use super::*;
+
+    use rust_genetics_browser::gwas::JsonReader;
+
+    const JSON: &str = r#"[{"chr":"1","ps":1000,"p_wald":0.01},{"chr":"1","p_wald":0.5},
+        {"chr":"2","ps":2000,"p_wald":0.001}]"#;
+
+    #[wasm_bindgen_test]
+    fn rows_across_chunks() {
+        let (first, rest) = JSON.split_at(45);
+
+        let mut reader = JsonReader::new();
+        let records = reader.push(first.as_bytes()).unwrap();
+        assert_eq!(records.len(), 1);
+
+        let err = reader.push(rest.as_bytes()).err().unwrap();
+        assert_eq!(err.to_string(), "row 2: missing field `ps`");
+    }
+
+    #[wasm_bindgen_test]
+    fn skip_bad_rows() {
+        let mut reader = JsonReader::new().skip_bad_rows(true);
+
+        let records: Vec<_> = JSON
+            .as_bytes()
+            .chunks(7)
+            .flat_map(|chunk| reader.push(chunk).unwrap())
+            .collect();
+        reader.finish().unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].pos, 2000);
+        assert_eq!(reader.skipped_rows(), 1);
+    }
+
+    #[wasm_bindgen_test]
+    fn zero_p_wald() {
+        let json = r#"[{"chr":"1","ps":1000,"p_wald":0},{"chr":"1","ps":2000,"p_wald":1.5}]"#;
+
+        let (first, rest) = json.split_at(40);
+
+        let mut reader = JsonReader::new();
+        let records = reader.push(first.as_bytes()).unwrap();
+        assert!((records[0].neg_log10_p - 323.306).abs() < 1e-3);
+
+        let err = reader.push(rest.as_bytes()).err().unwrap();
+        assert_eq!(err.to_string(), "row 2: invalid value `1.5` for `p_wald`");
+    }
+}
+
+mod locus {
+    use super::*;
+
+    use rust_genetics_browser::coordinates::{format_position, CoordinateSystem, Locus};
+
+    const PADDING: usize = 100;
+
+    fn coords() -> CoordinateSystem {
+        let json = r#"{
+            "name": "test",
+            "chrs": [
+                { "name": "1", "len": 1000 },
+                { "name": "2", "len": 500 },
+                { "name": "X", "len": 300 }
+            ]
+        }"#;
+
+        CoordinateSystem::parse_js(js_sys::JSON::parse(json).unwrap()).unwrap()
+    }
+
+    fn locus(chr: &str, start: usize, end: usize) -> Locus {
+        Locus {
+            chr: chr.to_string(),
+            start,
+            end,
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn parse() {
+        let coords = coords();
+
+        assert_eq!(coords.parse_locus("2:100-200"), Some(locus("2", 100, 200)));
+        assert_eq!(coords.parse_locus("CHRX:5"), Some(locus("X", 5, 6)));
+        assert_eq!(coords.parse_locus("x"), Some(locus("X", 0, 300)));
+        assert_eq!(
+            coords.parse_locus("1:0.5k-0.0006M"),
+            Some(locus("1", 500, 600))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn parse_suffixes() {
+        let json = r#"{ "name": "test", "chrs": [{ "name": "chr4", "len": 156508116 }] }"#;
+        let coords = CoordinateSystem::parse_js(js_sys::JSON::parse(json).unwrap()).unwrap();
+
+        let expected = Some(locus("chr4", 1_200_000, 1_350_000));
+
+        assert_eq!(coords.parse_locus("chr4:1,200,000-1,350,000"), expected);
+        assert_eq!(coords.parse_locus("4:1.2M-1.35M"), expected);
+        assert_eq!(coords.parse_locus("4:1200kb-1350kb"), expected);
+        assert_eq!(
+            coords.parse_locus("4:1200kb"),
+            Some(locus("chr4", 1_200_000, 1_200_001))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn parse_invalid() {
+        let coords = coords();
+
+        assert_eq!(coords.parse_locus("Un:1-2"), None);
+        assert_eq!(coords.parse_locus("1:200-100"), None);
+        assert_eq!(coords.parse_locus("1:2000-3000"), None);
+        assert_eq!(coords.parse_locus("1:a-b"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn format() {
+        assert_eq!(format_position(0), "0");
+        assert_eq!(format_position(999), "999");
+        assert_eq!(format_position(1_000), "1,000");
+        assert_eq!(format_position(1_234_567), "1,234,567");
+
+        let coords = coords();
+        let formatted = locus("1", 1_000, 2_000).to_string();
+
+        assert_eq!(formatted, "1:1,000-2,000");
+        assert_eq!(
+            coords.parse_locus(&locus("2", 10, 20).to_string()),
+            Some(locus("2", 10, 20))
+        );
+    }
+
+    #[wasm_bindgen_test]
+    fn convert() {
+        let coords = coords();
+
+        assert_eq!(coords.genome_pos(PADDING, "1", 10), Some(10));
+        assert_eq!(coords.genome_pos(PADDING, "2", 10), Some(1_110));
+        assert_eq!(coords.genome_pos(PADDING, "3", 10), None);
+
+        assert_eq!(coords.chr_pos(PADDING, 1_110), Some(("2", 10)));
+        assert_eq!(coords.chr_pos(PADDING, 1_799), Some(("X", 99)));
+        // in the padding, and past the last chromosome
+        assert_eq!(coords.chr_pos(PADDING, 1_050), None);
+        assert_eq!(coords.chr_pos(PADDING, 2_000), None);
+
+        assert_eq!(coords.genome_range(PADDING, &locus("2", 200, 100)), None);
+
+        let locus = locus("2", 100, 200);
+        assert_eq!(coords.genome_range(PADDING, &locus), Some((1_200, 1_300)));
+        assert_eq!(coords.locus_of_range(PADDING, (1_200, 1_300)), Some(locus));
+    }
+
+    #[wasm_bindgen_test]
+    fn round_trip() {
+        let coords = coords();
+
+        // the ends of the chromosomes either side of each boundary
+        let loci = [
+            locus("1", 0, 1),
+            locus("1", 999, 1_000),
+            locus("2", 0, 1),
+            locus("2", 499, 500),
+            locus("X", 0, 300),
+        ];
+
+        for locus in loci.iter() {
+            let range = coords.genome_range(PADDING, locus).unwrap();
+            assert_eq!(coords.locus_of_range(PADDING, range).as_ref(), Some(locus));
+        }
+
+        // a range across the boundary of 1 and 2 is taken to be on
+        // the chromosome its middle is on, and clamped to it
+        let across = coords.locus_of_range(PADDING, (900, 1_300)).unwrap();
+        assert_eq!(across, locus("2", 0, 200));
+        assert_eq!(coords.genome_range(PADDING, &across), Some((1_100, 1_300)));
+    }
+
+    #[wasm_bindgen_test]
+    fn clamp() {
+        let coords = coords();
+
+        assert_eq!(
+            coords.clamp(&locus("2", 400, 900)),
+            Some(locus("2", 400, 500))
+        );
+        assert_eq!(
+            coords.clamp(&locus("2", 600, 900)),
+            Some(locus("2", 500, 500))
+        );
+        assert_eq!(coords.clamp(&locus("3", 0, 1)), None);
+
+        assert_eq!(
+            coords.locus_of_range(PADDING, (900, 1_400)),
+            Some(locus("2", 0, 300))
+        );
+    }
+}
+
+mod chr_aliases {
+    use super::*;
+
+    use rust_genetics_browser::coordinates::CoordinateSystem;
+
+    fn mouse() -> CoordinateSystem {
+        let chrs: Vec<String> = (1..=19)
+            .map(|n| n.to_string())
+            .chain(vec!["X".to_string(), "Y".to_string(), "MT".to_string()])
+            .map(|name| format!(r#"{{ "name": "{}", "len": 1000 }}"#, name))
+            .collect();
+
+        let json = format!(r#"{{ "name": "GRCm38", "chrs": [{}] }}"#, chrs.join(","));
+
+        CoordinateSystem::parse_js(js_sys::JSON::parse(&json).unwrap()).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn builtin() {
+        let coords = mouse();
+
+        assert_eq!(coords.find_chr("1"), Some("1"));
+        assert_eq!(coords.find_chr("chr1"), Some("1"));
+        assert_eq!(coords.find_chr("chrx"), Some("X"));
+        assert_eq!(coords.find_chr("chrM"), Some("MT"));
+        assert_eq!(coords.find_chr("XY"), Some("X"));
+
+        // PLINK's numbering after the 19 autosomes
+        assert_eq!(coords.find_chr("20"), Some("X"));
+        assert_eq!(coords.find_chr("21"), Some("Y"));
+        assert_eq!(coords.find_chr("23"), Some("MT"));
+
+        assert_eq!(coords.find_chr("24"), None);
+        assert_eq!(coords.find_chr("chrUn"), None);
+    }
+
+    #[wasm_bindgen_test]
+    fn added() {
+        let mut coords = mouse();
+
+        assert!(coords.add_chr_alias("chrUn", "chr19"));
+        assert_eq!(coords.find_chr("UN"), Some("19"));
+
+        // added aliases take precedence over the built-in ones
+        assert!(coords.add_chr_alias("20", "Y"));
+        assert_eq!(coords.find_chr("20"), Some("Y"));
+
+        assert!(!coords.add_chr_alias("Z", "W"));
+        assert_eq!(coords.find_chr("Z"), None);
+    }
+}
+
+mod chr_records {
+    use super::*;
+
+    use rust_genetics_browser::gwas::binary::{decode, encode, Precision};
+    use rust_genetics_browser::gwas::{ChrRecords, GwasRecord};
+
+    #[wasm_bindgen_test]
+    fn indices_within() {
+        let records: Vec<GwasRecord> = [1000, 2000, 2000, 3000, 5000]
+            .iter()
+            .map(|&pos| GwasRecord::new("1", pos, 1.0))
+            .collect();
+
+        let bytes = encode(&records, Precision::F32).unwrap();
+        let columns = decode(&bytes).unwrap().remove(0).into_owned();
+
+        for records in [ChrRecords::Records(records), ChrRecords::Columns(columns)].iter() {
+            assert_eq!(records.indices_within(1500.0, 3000.0), 1..4);
+            assert_eq!(records.indices_within(2000.0, 2000.0), 1..3);
+            assert_eq!(records.indices_within(0.0, 999.0), 0..0);
+            assert_eq!(records.indices_within(5001.0, 9000.0), 5..5);
+            assert_eq!(records.indices_within(3000.0, 1000.0), 3..3);
+        }
+    }
+}